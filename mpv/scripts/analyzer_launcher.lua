@@ -1,4 +1,12 @@
+-- Keys: c cut, a replay, b/n previous/next line, k loop, y apply sub offset.
+-- None of them is an mpv default binding (r would take over sub-pos); rebind in input.conf
+-- with script-binding analyzer_launcher/<name>.
 local mp = require('mp')
+local options = require('mp.options')
+
+-- Override with script-opts=analyzer_launcher-loop_count=5
+local opts = { loop_count = 3 }
+options.read_options(opts, 'analyzer_launcher')
 
 local text = mp.get_property('sub-text') -- string or nil
 
@@ -19,4 +27,26 @@ end)
 mp.add_key_binding('c', 'analyzer-launcher', function()
     mp.commandv('script-message', 'cut_current_sub')
     
-end)
\ No newline at end of file
+end)
+
+-- Line navigation; the analyzer does the seeking and pauses at the end of the line
+mp.add_key_binding('a', 'replay-line', function()
+    mp.commandv('script-message', 'replay_line')
+end)
+
+mp.add_key_binding('b', 'prev-line', function()
+    mp.commandv('script-message', 'prev_line')
+end)
+
+mp.add_key_binding('n', 'next-line', function()
+    mp.commandv('script-message', 'next_line')
+end)
+
+mp.add_key_binding('k', 'loop-line', function()
+    mp.commandv('script-message', 'loop_line', tostring(opts.loop_count))
+end)
+
+-- Set sub-delay to the offset the analyzer measured over the recent cuts
+mp.add_key_binding('y', 'apply-sub-offset', function()
+    mp.commandv('script-message', 'apply_sub_offset')
+end)
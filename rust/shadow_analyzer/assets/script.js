@@ -84,6 +84,43 @@ function drawPitch(seriesHz, medianHz) {
   ctx.stroke();
 }
 
+// "-23.4 LUFS · TP -1.2 dBTP · 3 clipped" for one clip's loudness summary
+// plus the gain of the normalised playback copy, if one was written
+function formatLevel(l, norm) {
+  if (!l) return '';
+  var lufs = (typeof l.integrated_lufs === 'number') ? l.integrated_lufs.toFixed(1) + ' LUFS' : '— LUFS';
+  var out = lufs + ' · TP ' + l.true_peak_dbtp.toFixed(1) + ' dBTP';
+  if (l.clipped_samples > 0) out += ' · ' + l.clipped_samples + ' clipped';
+  if (norm) out += ' · play ' + (norm.gain_db >= 0 ? '+' : '') + norm.gain_db.toFixed(1) + ' dB';
+  return out;
+}
+
+// Short-term loudness contours on a shared -60..0 LUFS scale: source white, mic orange
+var loudnessContours = { src: null, mic: null, cut: null };
+// Normalised copies of the current cut, preferred for playback once known
+var normPaths = { src: null, mic: null };
+function drawLoudness() {
+  var cv = document.getElementById('loudness-canvas');
+  if (!cv) return;
+  var ctx = cv.getContext('2d');
+  ctx.clearRect(0, 0, cv.width, cv.height);
+  var w = cv.width, h = cv.height;
+  [['src', '#FFF'], ['mic', '#e5a50a']].forEach(function (pair) {
+    var series = loudnessContours[pair[0]];
+    if (!series || series.length < 2) return;
+    ctx.lineWidth = 2.0;
+    ctx.strokeStyle = pair[1];
+    ctx.beginPath();
+    for (var i = 0; i < series.length; i++) {
+      var x = (i / (series.length - 1)) * (w - 1);
+      var norm = Math.min(1, Math.max(0, (series[i] + 60) / 60));
+      var y = (1 - norm) * (h - 1);
+      if (i === 0) ctx.moveTo(x, y); else ctx.lineTo(x, y);
+    }
+    ctx.stroke();
+  });
+}
+
 window.addEventListener('analysis', function (e) {
   var d = e.detail || {};
   if ('text' in d) {
@@ -91,18 +128,72 @@ window.addEventListener('analysis', function (e) {
     if (!tv && typeof d.text === 'string') tv = d.text; // fallback if trimming emptied unexpectedly
     setText('text', tv);
   }
+  if ('translation' in d) setText('translation', d.translation ?? '');
+  if ('error' in d) setText('error', d.error ?? '');
+  if ('warnings' in d) setText('warnings', (d.warnings || []).join('; '));
   if ('s' in d && 'e' in d && typeof d.s === 'number' && typeof d.e === 'number') {
-    setText('window', d.s.toFixed(3) + '–' + d.e.toFixed(3));
+    var win = d.s.toFixed(3) + '–' + d.e.toFixed(3);
+    // Show any timing corrections that moved the cut
+    var corr = [];
+    if (typeof d.sub_delay === 'number' && d.sub_delay !== 0) corr.push('sub ' + (d.sub_delay > 0 ? '+' : '') + d.sub_delay.toFixed(3));
+    if (typeof d.audio_delay === 'number' && d.audio_delay !== 0) corr.push('audio ' + (d.audio_delay > 0 ? '+' : '') + d.audio_delay.toFixed(3));
+    if (typeof d.speed === 'number' && d.speed !== 1) corr.push(d.speed.toFixed(2) + '×');
+    if (corr.length) win += ' (' + corr.join(', ') + ')';
+    // Auto-trim moved the cut to the speech: show where the subtitle put it
+    if (d.trim) win += ' · subs ' + d.trim.orig_s.toFixed(3) + '–' + d.trim.orig_e.toFixed(3);
+    setText('window', win);
+  }
+  if ('sub_offset' in d) {
+    // "+0.312 s over 6 cuts (±0.021)"; Apply only once the cuts agree
+    var o = d.sub_offset;
+    var btnOffset = document.getElementById('apply-offset-button');
+    if (o) {
+      var txt = (o.offset >= 0 ? '+' : '') + o.offset.toFixed(3) + ' s over ' + o.cuts + ' cut' + (o.cuts === 1 ? '' : 's') + ' (±' + o.spread.toFixed(3) + ')';
+      if (!o.stable) txt += ' · settling';
+      setText('sub-offset', txt);
+    } else {
+      setText('sub-offset', '');
+    }
+    if (btnOffset) btnOffset.disabled = !(o && o.stable);
+  }
+  if ('ff_index' in d) {
+    // "1 · 5.1, centre": the source layout and where the analysed dialogue came from
+    var ff = String(d.ff_index);
+    if (d.dialogue) ff += ' · ' + d.dialogue.layout + ', ' + d.dialogue.method;
+    setText('ff', ff);
   }
-  if ('ff_index' in d) setText('ff', String(d.ff_index));
   if ('latency_ms' in d) setText('lat', (d.latency_ms != null ? d.latency_ms : '') + (d.latency_ms != null ? ' ms' : ''));
   if ('rms' in d && typeof d.rms === 'number') setText('rms', d.rms.toFixed(4));
   if ('peak' in d && typeof d.peak === 'number') setText('peak', d.peak.toFixed(4));
+  // A new cut clears the mic level until its take is measured
+  if (d.out_path && d.out_path !== loudnessContours.cut) {
+    loudnessContours = { src: null, mic: null, cut: d.out_path };
+    normPaths = { src: null, mic: null };
+    var btnEnh = document.getElementById('play-enh-button');
+    if (btnEnh) { btnEnh.disabled = true; btnEnh.textContent = 'Play'; }
+    setText('lvl-src', '');
+    setText('lvl-mic', '');
+    drawLoudness();
+  }
+  if (d.loudness_src) {
+    setText('lvl-src', formatLevel(d.loudness_src, d.norm_src));
+    loudnessContours.src = d.loudness_src.short_term;
+    drawLoudness();
+  }
+  if (d.loudness_mic) {
+    setText('lvl-mic', formatLevel(d.loudness_mic, d.norm_mic));
+    loudnessContours.mic = d.loudness_mic.short_term;
+    drawLoudness();
+  }
 
   // F0 numbers
   if (typeof d.f0_src_median === 'number') setText('f0src', d.f0_src_median.toFixed(1) + ' Hz');
   if (typeof d.f0_mic_median === 'number') setText('f0mic', d.f0_mic_median.toFixed(1) + ' Hz');
-  if (typeof d.voiced_src === 'number') setText('voiced', Math.round(d.voiced_src * 100) + '%');
+  if (typeof d.voiced_src === 'number') {
+    var voiced = Math.round(d.voiced_src * 100) + '%';
+    if (typeof d.voicing_src === 'number') voiced += ' · p ' + d.voicing_src.toFixed(2);
+    setText('voiced', voiced);
+  }
 
   // Pitch graph from source series
   if (Array.isArray(d.f0_src_series)) {
@@ -117,10 +208,13 @@ window.addEventListener('analysis', function (e) {
   var btnBoth = document.getElementById('play-both-button');
   if (btnBoth) btnBoth.textContent = 'Play';
 
-  // Source player: prefer latest_path if present
+  if (d.norm_src) normPaths.src = d.norm_src.path;
+  if (d.norm_mic) normPaths.mic = d.norm_mic.path;
+
+  // Source player: prefer the normalised copy, then latest_path
   var player = document.getElementById('player');
   if (player && (d.latest_path || d.out_path)) {
-    var chosen = d.latest_path || d.out_path;
+    var chosen = normPaths.src || d.latest_path || d.out_path;
     var url = 'file:///' + String(chosen).replace(/\\/g, '/');
     var ts = Date.now();
     player.src = encodeURI(url + '?t=' + ts);
@@ -128,10 +222,20 @@ window.addEventListener('analysis', function (e) {
     player.load();
   }
 
+  // Enhanced source (what the F0 was taken from), once written
+  var playerEnh = document.getElementById('player-enh');
+  if (playerEnh && d.enhanced) {
+    playerEnh.src = encodeURI('file:///' + String(d.enhanced.path).replace(/\\/g, '/') + '?t=' + Date.now());
+    try { playerEnh.pause(); playerEnh.currentTime = 0; } catch (_) {}
+    playerEnh.load();
+    var btnEnh2 = document.getElementById('play-enh-button');
+    if (btnEnh2) { btnEnh2.disabled = false; btnEnh2.textContent = 'Play'; }
+  }
+
   // Mic player: update when latest_mic_path present
   var playerMic = document.getElementById('player-mic');
   if (playerMic && d.latest_mic_path) {
-    var urlm = 'file:///' + String(d.latest_mic_path).replace(/\\/g, '/');
+    var urlm = 'file:///' + String(normPaths.mic || d.latest_mic_path).replace(/\\/g, '/');
     var ts2 = Date.now();
     playerMic.src = encodeURI(urlm + '?t=' + ts2);
     try { playerMic.pause(); playerMic.currentTime = 0; } catch (_) {}
@@ -139,6 +243,16 @@ window.addEventListener('analysis', function (e) {
   }
 });
 
+// Ask the analyzer to set mpv's sub-delay to the estimate
+function applySubOffset() {
+  if (window.ipc && typeof window.ipc.postMessage === 'function') {
+    window.ipc.postMessage(JSON.stringify({ type: 'apply_sub_offset' }));
+  }
+  setText('sub-offset', 'applied; next cuts measure what is left');
+  var btn = document.getElementById('apply-offset-button');
+  if (btn) btn.disabled = true;
+}
+
 function togglePlayGroup(players, buttons) {
   if (!buttons || !buttons.length) return;
   var isPlay = buttons[0] && buttons[0].textContent === 'Play';
@@ -198,6 +312,19 @@ function togglePlayMic() {
   });
 })();
 
+function togglePlayEnh() {
+  togglePlayGroup([document.getElementById('player-enh')], [document.getElementById('play-enh-button')]);
+}
+
+(function attachEndedHandlerEnh() {
+  var player = document.getElementById('player-enh');
+  if (!player) return;
+  player.addEventListener('ended', function () {
+    var button = document.getElementById('play-enh-button');
+    if (button) button.textContent = 'Play';
+  });
+})();
+
 (function setupMicSelector() {
   var sel = document.getElementById('mic-selector');
   if (!sel) return;
@@ -232,6 +359,14 @@ window.addEventListener('devices', function (e) {
   }
 });
 
+window.addEventListener('connection', function (e) {
+  var d = e.detail || {};
+  var label = d.connected ? 'connected' : 'waiting for mpv…';
+  setText('conn', label + (d.endpoint ? ' (' + d.endpoint + ')' : ''));
+  var el = document.getElementById('conn');
+  if (el) el.style.color = d.connected ? '' : '#e5a50a';
+});
+
 window.addEventListener('play-both', function (e) {
   togglePlayBoth();
 });
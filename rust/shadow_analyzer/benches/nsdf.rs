@@ -0,0 +1,32 @@
+// MPM's NSDF through the FFT autocorrelation against the direct O(N · lags) sum it replaced,
+// for one 40 ms frame at the analysis rate. Only the NSDF is timed: no peak picking or voicing.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+// The bin crate's modules, built into the bench as they are
+#[allow(dead_code)]
+#[path = "../src/cli.rs"]
+mod cli;
+#[allow(dead_code)]
+#[path = "../src/pitch.rs"]
+mod pitch;
+
+fn bench_nsdf(c: &mut Criterion) {
+    let cfg = pitch::F0Config::default();
+    let (tau_min, tau_max) = pitch::lag_range(&cfg);
+    // Two harmonics, as in a voiced frame
+    let frame: Vec<f32> = (0..cfg.frame_size).map(|i| {
+        let t = std::f32::consts::TAU * i as f32 / cfg.sample_rate_hz;
+        0.3 * (97.0 * t).sin() + 0.2 * (194.0 * t).sin()
+    }).collect();
+
+    let mut group = c.benchmark_group(format!("nsdf {} lags of {} samples", tau_max - tau_min + 1, cfg.frame_size));
+    let mut nsdf = vec![0.0f32; tau_max + 1];
+    group.bench_function("direct", |b| b.iter(|| pitch::nsdf_direct(black_box(&frame), tau_min, tau_max, &mut nsdf)));
+    let mut mpm = pitch::Mpm::new(&cfg);
+    group.bench_function("fft", |b| b.iter(|| mpm.compute_nsdf(black_box(&frame)).map(|n| n[tau_max])));
+    group.finish();
+}
+
+criterion_group!(benches, bench_nsdf);
+criterion_main!(benches);
@@ -0,0 +1,238 @@
+// Clip analysis shared by the live (mpv) and headless paths:
+// F0 with energy gating and gap bridging, plus simple level metrics.
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{pitch, resample, wav};
+
+// Sample rate the F0 tracker runs at (decoded windows are decimated to this)
+pub const ANALYSIS_SAMPLE_RATE: u32 = 24000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct F0Summary {
+    // Pitch estimator that produced it ("mpm", "yin", "pyin")
+    pub method: &'static str,
+    pub median_hz: Option<f32>,
+    // Voiced share of the unpadded interior, after gating and bridging
+    pub voiced_ratio: f32,
+    // Mean voicing confidence of the same frames, 0–1 (pitch::F0Result::voicing)
+    pub voicing: f32,
+    // At most 64 points (Hz, 0.0 = unvoiced) for drawing
+    pub series: Vec<f32>,
+}
+
+// Mono mixdown of an interleaved buffer, resampled to the F0 rate
+pub fn analysis_mono(samples: &[f32], channels: u16, sample_rate: u32) -> (Vec<f32>, u32) {
+    let ch = channels.max(1) as usize;
+    let mono: Vec<f32> = samples.chunks_exact(ch).map(|f| f.iter().sum::<f32>() / ch as f32).collect();
+    (resample::resample(&mono, sample_rate, ANALYSIS_SAMPLE_RATE), ANALYSIS_SAMPLE_RATE)
+}
+
+pub fn summarize_f0(mono: &[f32], sample_rate: u32, edge_pad_s: f32, method: pitch::PitchMethod) -> F0Summary {
+    let cfg = pitch::F0Config { sample_rate_hz: sample_rate as f32, method, ..Default::default() };
+    summarize(pitch::estimate_f0(mono, &cfg), &cfg, edge_pad_s)
+}
+
+// The same summary for a WAV of any length (a mic take), streamed block by block through
+// the mono mixdown, the resampler and the F0 tracker. `on_mono` sees every mono block at the
+// file's rate, so the caller can measure the same read.
+pub fn summarize_f0_reader<F: FnMut(&[f32])>(
+    reader: &mut wav::WavReader,
+    edge_pad_s: f32,
+    method: pitch::PitchMethod,
+    mut on_mono: F,
+) -> Result<F0Summary> {
+    let cfg = pitch::F0Config { sample_rate_hz: ANALYSIS_SAMPLE_RATE as f32, method, ..Default::default() };
+    let info = reader.info;
+    let mut resampler = resample::Resampler::new(info.sample_rate, ANALYSIS_SAMPLE_RATE);
+    let mut stream = pitch::F0Stream::new(&cfg);
+    let mut mono = Vec::new();
+    for block in reader.blocks(1 << 14) {
+        let block = info.mix_mono(&block?);
+        on_mono(&block);
+        mono.clear();
+        resampler.push(&block, &mut mono);
+        stream.push(&mono);
+    }
+    mono.clear();
+    resampler.finish(&mut mono);
+    stream.push(&mono);
+    Ok(summarize(stream.finish(), &cfg, edge_pad_s))
+}
+
+fn summarize(r: pitch::F0Result, cfg: &pitch::F0Config, edge_pad_s: f32) -> F0Summary {
+    // Energy gate: per-frame RMS and noise floor
+    let rms_vec = &r.frame_rms;
+    let mut gated = r.f0_hz.clone();
+    if !rms_vec.is_empty() {
+        let mut rms_sorted = rms_vec.clone();
+        rms_sorted.sort_by(|a,b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let idx = ((rms_sorted.len() as f32) * 0.20).floor() as usize;
+        let idx = idx.min(rms_sorted.len().saturating_sub(1));
+        let noise_floor = rms_sorted[idx];
+        let thresh = noise_floor * 1.6; // ~+4 dB above floor
+        let m = gated.len().min(rms_vec.len());
+        for i in 0..m {
+            if rms_vec[i] < thresh { gated[i] = 0.0; }
+        }
+    }
+    let bridged = bridge_gaps(gated, 2);
+    // Compute voiced ratio excluding padded edges on bridged series
+    let hop_s = (cfg.hop_size as f32 / cfg.sample_rate_hz).max(1e-6);
+    let margin_frames = ((edge_pad_s.max(0.0) / hop_s).ceil() as usize).min(bridged.len());
+    let (interior_ratio, interior) = if bridged.len() > 2 * margin_frames {
+        let interior = margin_frames..bridged.len() - margin_frames;
+        let slice = &bridged[interior.clone()];
+        let voiced = slice.iter().filter(|x| **x > 0.0).count() as f32;
+        (voiced / (slice.len() as f32), interior)
+    } else { (r.voiced_ratio, 0..bridged.len()) };
+    // Mean voicing confidence over the same frames
+    let voicing = r.voicing.get(interior).filter(|v| !v.is_empty())
+        .map_or(0.0, |v| v.iter().sum::<f32>() / v.len() as f32);
+    // Downsample series to at most 64 points (for drawing)
+    let series = downsample_series(&bridged, 64);
+    F0Summary { method: cfg.method.name(), median_hz: r.median_hz, voiced_ratio: interior_ratio, voicing, series }
+}
+
+// Bridge tiny unvoiced gaps (<= max_gap frames) by linear interpolation
+pub fn bridge_gaps(mut bridged: Vec<f32>, max_gap: usize) -> Vec<f32> {
+    let n = bridged.len();
+    let mut i0 = 0usize;
+    while i0 < n {
+        if bridged[i0] > 0.0 { i0 += 1; continue; }
+        let start = i0;
+        while i0 < n && bridged[i0] == 0.0 { i0 += 1; }
+        let end = i0; // exclusive
+        let gap_len = end - start;
+        if gap_len > 0 && gap_len <= max_gap {
+            let prev_opt = if start > 0 && bridged[start - 1] > 0.0 { Some(start - 1) } else { None };
+            let next_opt = if end < n && bridged[end] > 0.0 { Some(end) } else { None };
+            if let (Some(a), Some(b)) = (prev_opt, next_opt) {
+                let a_val = bridged[a];
+                let b_val = bridged[b];
+                for k in 0..gap_len {
+                    let t = (k as f32 + 1.0) / (gap_len as f32 + 1.0);
+                    bridged[start + k] = a_val * (1.0 - t) + b_val * t;
+                }
+            }
+        }
+    }
+    bridged
+}
+
+// RMS and absolute peak over a whole buffer (interleaved channels are fine)
+pub fn rms_peak(samples: &[f32]) -> (f32, f32) {
+    let mut sum_sq: f64 = 0.0;
+    let mut peak_abs: f32 = 0.0;
+    for &x in samples {
+        let ax = x.abs();
+        if ax > peak_abs { peak_abs = ax; }
+        sum_sq += (x as f64) * (x as f64);
+    }
+    let rms = if !samples.is_empty() {
+        (sum_sq / samples.len() as f64).sqrt() as f32
+    } else { 0.0 };
+    (rms, peak_abs)
+}
+
+// Downsample by picking evenly spaced indices up to max_len
+pub fn downsample_series(src: &[f32], max_len: usize) -> Vec<f32> {
+    if src.is_empty() || max_len == 0 { return Vec::new(); }
+    if src.len() <= max_len { return src.to_vec(); }
+    let n = src.len();
+    let m = max_len;
+    let mut out = Vec::with_capacity(m);
+    for i in 0..m {
+        let idx = ((i as f32) * ((n - 1) as f32) / ((m - 1) as f32)).round() as usize;
+        out.push(src[idx.min(n - 1)]);
+    }
+    out
+}
+
+// Per-frame RMS with given frame and hop sizes
+pub fn frame_rms(samples: &[f32], frame_size: usize, hop_size: usize) -> Vec<f32> {
+    if samples.is_empty() || frame_size == 0 { return Vec::new(); }
+    let mut out: Vec<f32> = Vec::new();
+    let mut start = 0usize;
+    while start + frame_size <= samples.len() {
+        let mut sum_sq: f64 = 0.0;
+        for i in 0..frame_size {
+            let v = samples[start + i] as f64;
+            sum_sq += v * v;
+        }
+        let rms = (sum_sq / frame_size as f64).sqrt() as f32;
+        out.push(rms);
+        start = start.saturating_add(hop_size.max(1));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bridge_gaps_only_short_interior() {
+        let src = vec![0.0, 100.0, 0.0, 0.0, 130.0, 0.0, 0.0, 0.0, 90.0, 0.0];
+        let out = bridge_gaps(src, 2);
+        assert_eq!(out[0], 0.0); // leading gap has no left neighbour
+        assert!((out[2] - 110.0).abs() < 1e-3 && (out[3] - 120.0).abs() < 1e-3);
+        assert_eq!(&out[5..8], &[0.0, 0.0, 0.0]); // 3-frame gap stays unvoiced
+        assert_eq!(out[9], 0.0);
+    }
+
+    #[test]
+    fn test_analysis_mono_mixdown_and_resampling() {
+        // L + R sums to a constant 2.0, i.e. 1.0 after the mixdown
+        let stereo: Vec<f32> = (0..4800).flat_map(|i| [i as f32 / 4800.0, 2.0 - i as f32 / 4800.0]).collect();
+        let (m, sr) = analysis_mono(&stereo, 2, 48000);
+        assert_eq!((m.len(), sr), (2400, ANALYSIS_SAMPLE_RATE));
+        assert!(m[100..2300].iter().all(|x| (x - 1.0).abs() < 1e-3));
+        let (m, sr) = analysis_mono(&[0.5; 4410], 1, 44100);
+        assert_eq!((m.len(), sr), (2400, ANALYSIS_SAMPLE_RATE));
+        let (m, _) = analysis_mono(&[0.5; 2400], 1, ANALYSIS_SAMPLE_RATE);
+        assert_eq!(m, vec![0.5; 2400]);
+    }
+
+    #[test]
+    fn test_summarize_f0_tone_with_silent_edges() {
+        let sr = ANALYSIS_SAMPLE_RATE;
+        let pad = (0.1 * sr as f32) as usize;
+        let mut sig = vec![0.0f32; pad];
+        sig.extend((0..sr as usize / 2).map(|i| {
+            (2.0 * std::f32::consts::PI * 180.0 * i as f32 / sr as f32).sin() * 0.5
+        }));
+        sig.extend(std::iter::repeat_n(0.0, pad));
+        for method in [pitch::PitchMethod::Mpm, pitch::PitchMethod::Yin, pitch::PitchMethod::Pyin] {
+            let s = summarize_f0(&sig, sr, 0.10, method);
+            assert_eq!(s.method, method.name());
+            let med = s.median_hz.expect("median");
+            assert!((med - 180.0).abs() < 3.0, "{:?} median={}", method, med);
+            assert!(s.voiced_ratio > 0.8, "{:?} voiced={}", method, s.voiced_ratio);
+            assert!(s.voicing > 0.8 && s.voicing <= 1.0, "{:?} voicing={}", method, s.voicing);
+            assert!(s.series.len() <= 64);
+        }
+    }
+
+    #[test]
+    fn test_summarize_f0_reader_matches_in_memory() {
+        let path = std::env::temp_dir().join(format!("mpvshadow_analysis_{}.wav", std::process::id()));
+        // 48 kHz stereo: the mixdown and the resampler run on the blocks too
+        let tone = (0..48000).flat_map(|i| {
+            let x = if (4800..43200).contains(&i) { (2.0 * std::f32::consts::PI * 150.0 * i as f32 / 48000.0).sin() * 0.5 } else { 0.0 };
+            [x, x]
+        });
+        let spec = wav::WavSpec { sample_rate: 48000, channels: 2, format: wav::SampleFormat::Pcm16 };
+        wav::write(&path, &tone.collect::<Vec<f32>>(), spec, &Default::default()).unwrap();
+        let (mono, sr) = wav::read_wav_mono(&path, Some(ANALYSIS_SAMPLE_RATE)).unwrap();
+        let whole = summarize_f0(&mono, sr, 0.10, pitch::PitchMethod::Mpm);
+        let mut reader = wav::WavReader::open(&path).unwrap();
+        let mut frames = 0;
+        let streamed = summarize_f0_reader(&mut reader, 0.10, pitch::PitchMethod::Mpm, |b| frames += b.len()).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(frames, 48000);
+        assert!((streamed.median_hz.expect("median") - 150.0).abs() < 3.0);
+        assert_eq!((streamed.median_hz, streamed.voiced_ratio, &streamed.series), (whole.median_hz, whole.voiced_ratio, &whole.series));
+    }
+}
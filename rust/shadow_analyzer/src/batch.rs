@@ -0,0 +1,320 @@
+// Batch sentence mining: cut every cue of an external subtitle file out of one
+// media file, in parallel, with the same naming and padding as the live path.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use anyhow::{Result, Context};
+use serde::Serialize;
+
+use crate::cut::{self, CutRequest, CutResult};
+use crate::{cli, decode, dialogue, enhance, loudness, pitch, subs, wav};
+
+#[derive(Debug, Clone)]
+pub struct BatchRequest {
+    pub media_path: String,
+    pub subs_path: PathBuf,
+    // Optional second subtitle file (translation) paired to each cue by overlap
+    pub translation_path: Option<PathBuf>,
+    pub ff_index: Option<u64>,
+    pub pad_s: f64,
+    pub out_dir: PathBuf,
+    pub decoder: decode::Decoder,
+    pub format: wav::SampleFormat,
+    // Target LUFS for the `_norm.wav` copies, if any
+    pub normalize_lufs: Option<f32>,
+    pub auto_trim: bool,
+    // Shared by all workers (the ONNX runner serialises its model calls)
+    pub enhancer: Option<std::sync::Arc<dyn enhance::Enhancer>>,
+    pub dialogue: dialogue::DialogueMode,
+    pub pitch: pitch::PitchMethod,
+    pub jobs: usize,
+    // Inclusive 1-based cue numbers
+    pub lines: Option<(usize, usize)>,
+    // Keep cues overlapping [from_s, to_s]
+    pub from_s: Option<f64>,
+    pub to_s: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchEntry {
+    pub index: usize,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub translation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<CutResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub fn select_cues(cues: Vec<subs::SubCue>, req: &BatchRequest) -> Vec<subs::SubCue> {
+    cues.into_iter()
+        .filter(|c| req.lines.is_none_or(|(a, b)| c.index >= a && c.index <= b))
+        .filter(|c| req.from_s.is_none_or(|t| c.end_s >= t))
+        .filter(|c| req.to_s.is_none_or(|t| c.start_s <= t))
+        .collect()
+}
+
+// Cues sharing a window (e.g. two speakers on one ASS event) would write the
+// same clip file; keep one job per window with the texts joined
+pub fn merge_same_window(cues: Vec<subs::SubCue>, pad_s: f64) -> Vec<subs::SubCue> {
+    let mut out: Vec<subs::SubCue> = Vec::with_capacity(cues.len());
+    for c in cues {
+        let key = |x: &subs::SubCue| {
+            let (s, e) = cut::pad_window(x.start_s, x.end_s, 0.0, pad_s);
+            ((s * 1000.0).round() as u64, (e * 1000.0).round() as u64)
+        };
+        if let Some(prev) = out.iter_mut().find(|p| key(p) == key(&c)) {
+            prev.text.push('\n');
+            prev.text.push_str(&c.text);
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+// Cut and analyze every selected cue; entries come back in cue order
+pub fn run_batch(req: &BatchRequest) -> Result<Vec<BatchEntry>> {
+    if !Path::new(&req.media_path).exists() {
+        anyhow::bail!("media not found: {}", req.media_path);
+    }
+    let cues = subs::parse_file(&req.subs_path)?;
+    let cues = merge_same_window(select_cues(cues, req), req.pad_s);
+    if cues.is_empty() {
+        anyhow::bail!("no cues left after filtering");
+    }
+    let translations = match &req.translation_path {
+        Some(p) => subs::parse_file(p)?,
+        None => Vec::new(),
+    };
+    std::fs::create_dir_all(&req.out_dir)
+        .with_context(|| format!("create out dir: {}", req.out_dir.display()))?;
+
+    let total = cues.len();
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let jobs = req.jobs.clamp(1, total);
+    // Each worker hands back its own entries; joined here, so a panic stays in its worker
+    let per_worker: Vec<std::thread::Result<Vec<(usize, BatchEntry)>>> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs).map(|_| scope.spawn(|| {
+            let mut entries = Vec::new();
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(cue) = cues.get(i) else { break };
+                let translation = subs::overlapping_text(&translations, cue.start_s, cue.end_s);
+                let cut_req = CutRequest {
+                    media_path: req.media_path.clone(),
+                    start_s: cue.start_s,
+                    end_s: cue.end_s,
+                    ff_index: req.ff_index,
+                    pad_s: req.pad_s,
+                    out_dir: req.out_dir.clone(),
+                    decoder: req.decoder,
+                    format: req.format,
+                    text: Some(cue.text.clone()),
+                    translation: translation.clone(),
+                    normalize_lufs: req.normalize_lufs,
+                    auto_trim: req.auto_trim,
+                    enhancer: req.enhancer.clone(),
+                    dialogue: req.dialogue,
+                    pitch: req.pitch,
+                };
+                let (result, error) = match cut::run_cut(&cut_req) {
+                    Ok(r) => (Some(r), None),
+                    Err(e) => (None, Some(format!("{:#}", e))),
+                };
+                let n = done.fetch_add(1, Ordering::Relaxed) + 1;
+                eprintln!("[{}/{}] line {} {}", n, total, cue.index, if error.is_some() { "failed" } else { "ok" });
+                entries.push((i, BatchEntry { index: cue.index, text: cue.text.clone(), translation, result, error }));
+            }
+            entries
+        })).collect();
+        workers.into_iter().map(|w| w.join()).collect()
+    });
+    let mut results: Vec<Option<BatchEntry>> = vec![None; total];
+    for (i, entry) in per_worker.into_iter().flatten().flatten() {
+        results[i] = Some(entry);
+    }
+    // The cue a worker panicked on is reported as failed rather than left out
+    Ok(results.into_iter().zip(&cues).map(|(entry, cue)| entry.unwrap_or_else(|| BatchEntry {
+        index: cue.index,
+        text: cue.text.clone(),
+        translation: None,
+        result: None,
+        error: Some("batch worker panicked".to_string()),
+    })).collect())
+}
+
+pub const BATCH_USAGE: &str = "usage: shadow_analyzer batch --media <file> --subs <srt|ass|vtt> \
+[--subs2 <translation subs>] [--track <ff-index>] [--pad <sec>] [--out-dir <dir>] [--decoder auto|native|ffmpeg] [--format pcm8|pcm16|pcm24|pcm32|float|double] [--normalize <LUFS>] [--auto-trim] [--enhance wiener|onnx [--enhance-model <file.onnx>] [--enhance-rate <Hz>]] [--dialogue analysis|clip] [--pitch mpm|yin|pyin] [--jobs <n>] [--lines <a>-<b>] [--from <sec>] [--to <sec>]";
+
+// `shadow_analyzer batch ...`: one JSON line per cue on stdout, returns the exit code
+pub fn run_cli(args: &[String]) -> i32 {
+    let req = match parse_batch_args(args) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("{:#}\n{}", e, BATCH_USAGE);
+            return 2;
+        }
+    };
+    let entries = match run_batch(&req) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("batch failed: {:#}", e);
+            return 1;
+        }
+    };
+    let failed = entries.iter().filter(|e| e.error.is_some()).count();
+    for entry in &entries {
+        match serde_json::to_string(entry) {
+            Ok(js) => println!("{}", js),
+            Err(e) => eprintln!("serialize line {}: {}", entry.index, e),
+        }
+    }
+    eprintln!("batch: {} cut, {} failed -> {}", entries.len() - failed, failed, req.out_dir.display());
+    if failed > 0 { 1 } else { 0 }
+}
+
+fn parse_batch_args(args: &[String]) -> Result<BatchRequest> {
+    let media_path = cli::cli_value(args, "--media").context("missing --media")?;
+    let subs_path = cli::cli_value(args, "--subs").map(PathBuf::from).context("missing --subs")?;
+    let translation_path = cli::cli_value(args, "--subs2").map(PathBuf::from);
+    let ff_index = match cli::cli_value(args, "--track") {
+        Some(v) => Some(v.parse::<u64>().with_context(|| format!("invalid --track: {}", v))?),
+        None => None,
+    };
+    let pad_s = cli::parse_f64(args, "--pad")?.unwrap_or(cut::DEFAULT_PAD_S).max(0.0);
+    // Default: shadow_out/<media basename>/, kept apart from the live clips and their retention
+    let out_dir = cli::cli_value(args, "--out-dir").map(PathBuf::from).unwrap_or_else(|| {
+        let base = Path::new(&media_path).file_stem().and_then(|s| s.to_str()).unwrap_or("batch");
+        cut::default_out_dir().join(base)
+    });
+    let decoder = decode::Decoder::resolve(cli::cli_value(args, "--decoder").as_deref())?;
+    let format = cut::parse_format(args)?;
+    let normalize_lufs = loudness::parse_target(args)?;
+    let auto_trim = args.iter().any(|a| a == "--auto-trim");
+    let enhancer = enhance::parse_enhancer(args)?;
+    let dialogue = dialogue::DialogueMode::parse(args)?;
+    let pitch = pitch::parse_method(args)?;
+    let jobs = match cli::cli_value(args, "--jobs") {
+        Some(v) => v.parse::<usize>().with_context(|| format!("invalid --jobs: {}", v))?.max(1),
+        None => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+    };
+    let lines = match cli::cli_value(args, "--lines") {
+        Some(v) => Some(parse_line_range(&v).with_context(|| format!("invalid --lines: {}", v))?),
+        None => None,
+    };
+    let from_s = cli::parse_f64(args, "--from")?;
+    let to_s = cli::parse_f64(args, "--to")?;
+    Ok(BatchRequest { media_path, subs_path, translation_path, ff_index, pad_s, out_dir, decoder, format, normalize_lufs, auto_trim, enhancer, dialogue, pitch, jobs, lines, from_s, to_s })
+}
+
+// "12-40", "12-" (to the end) or "7" (single line)
+fn parse_line_range(s: &str) -> Option<(usize, usize)> {
+    match s.split_once('-') {
+        Some((a, b)) => {
+            let a = a.trim().parse::<usize>().ok()?;
+            let b = if b.trim().is_empty() { usize::MAX } else { b.trim().parse::<usize>().ok()? };
+            if a <= b { Some((a, b)) } else { None }
+        }
+        None => {
+            let n = s.trim().parse::<usize>().ok()?;
+            Some((n, n))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(index: usize, s: f64, e: f64, text: &str) -> subs::SubCue {
+        subs::SubCue { index, start_s: s, end_s: e, text: text.to_string() }
+    }
+
+    fn req() -> BatchRequest {
+        BatchRequest {
+            media_path: String::new(),
+            subs_path: PathBuf::new(),
+            translation_path: None,
+            ff_index: None,
+            pad_s: cut::DEFAULT_PAD_S,
+            out_dir: PathBuf::new(),
+            decoder: decode::Decoder::Auto,
+            format: wav::SampleFormat::Pcm16,
+            normalize_lufs: None,
+            auto_trim: false,
+            enhancer: None,
+            dialogue: dialogue::DialogueMode::Off,
+            pitch: pitch::PitchMethod::Mpm,
+            jobs: 1,
+            lines: None,
+            from_s: None,
+            to_s: None,
+        }
+    }
+
+    #[test]
+    fn test_select_by_lines_and_time() {
+        let cues = vec![cue(1, 1.0, 2.0, "a"), cue(2, 5.0, 6.0, "b"), cue(3, 9.0, 10.0, "c")];
+        let r = BatchRequest { lines: Some((2, usize::MAX)), ..req() };
+        assert_eq!(select_cues(cues.clone(), &r).len(), 2);
+        let r = BatchRequest { from_s: Some(5.5), to_s: Some(9.5), ..req() };
+        let got: Vec<usize> = select_cues(cues, &r).iter().map(|c| c.index).collect();
+        assert_eq!(got, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_merge_same_window() {
+        let cues = vec![cue(1, 1.0, 2.0, "a"), cue(2, 1.0, 2.0, "b"), cue(3, 3.0, 4.0, "c")];
+        let merged = merge_same_window(cues, 0.1);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].text, "a\nb");
+    }
+
+    #[derive(Debug)]
+    struct PanickingEnhancer;
+
+    impl enhance::Enhancer for PanickingEnhancer {
+        fn name(&self) -> &'static str { "panicking" }
+        fn enhance(&self, _input: &[f32], _sample_rate: u32) -> Result<Vec<f32>> {
+            panic!("enhancer bug")
+        }
+    }
+
+    #[test]
+    fn test_run_batch_reports_cues_of_a_panicked_worker() {
+        let dir = std::env::temp_dir().join(format!("mpvshadow_batch_panic_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let media = dir.join("tone.wav");
+        let tone: Vec<f32> = (0..96000).map(|i| 0.3 * (i as f32 * 200.0 * std::f32::consts::TAU / 48000.0).sin()).collect();
+        let spec = wav::WavSpec { sample_rate: 48000, channels: 1, format: wav::SampleFormat::Pcm16 };
+        wav::write(&media, &tone, spec, &Default::default()).unwrap();
+        let subs_path = dir.join("tone.srt");
+        std::fs::write(&subs_path, "1\n00:00:00,200 --> 00:00:00,800\none\n\n2\n00:00:01,000 --> 00:00:01,600\ntwo\n\n3\n00:00:01,700 --> 00:00:01,900\nthree\n").unwrap();
+        let r = BatchRequest {
+            media_path: media.to_string_lossy().to_string(),
+            subs_path,
+            out_dir: dir.join("out"),
+            decoder: decode::Decoder::Native,
+            enhancer: Some(std::sync::Arc::new(PanickingEnhancer)),
+            jobs: 2,
+            ..req()
+        };
+        let entries = run_batch(&r).unwrap();
+        // Every cue is there, in order, and marked failed
+        assert_eq!(entries.iter().map(|e| e.index).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!(entries.iter().all(|e| e.result.is_none() && e.error.as_deref() == Some("batch worker panicked")), "{:?}", entries);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_line_range() {
+        assert_eq!(parse_line_range("3-8"), Some((3, 8)));
+        assert_eq!(parse_line_range("4-"), Some((4, usize::MAX)));
+        assert_eq!(parse_line_range("7"), Some((7, 7)));
+        assert_eq!(parse_line_range("9-2"), None);
+    }
+}
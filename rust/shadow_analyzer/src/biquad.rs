@@ -0,0 +1,36 @@
+// Second-order IIR sections for the time-domain filters (K-weighting in loudness.rs,
+// the speech band in vad.rs).
+
+// Direct form II transposed, a0 normalised to 1
+pub struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    pub fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self { b, a, z: [0.0; 2] }
+    }
+
+    // Second-order high-pass / low-pass (bilinear transform, prewarped at f0)
+    pub fn highpass(fs: f64, f0: f64, q: f64) -> Self {
+        let k = (std::f64::consts::PI * f0 / fs).tan();
+        let a0 = 1.0 + k / q + k * k;
+        Self::new([1.0 / a0, -2.0 / a0, 1.0 / a0], [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0])
+    }
+
+    pub fn lowpass(fs: f64, f0: f64, q: f64) -> Self {
+        let k = (std::f64::consts::PI * f0 / fs).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let b = k * k / a0;
+        Self::new([b, 2.0 * b, b], [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0])
+    }
+
+    pub fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
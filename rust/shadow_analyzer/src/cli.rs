@@ -0,0 +1,26 @@
+// Tiny command-line helpers (no argument-parser dependency).
+
+use anyhow::{Result, Context};
+
+// Value following `--flag` (or given as `--flag=value`) on the command line
+pub fn cli_value(args: &[String], flag: &str) -> Option<String> {
+    let prefix = format!("{}=", flag);
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        if a == flag { return it.next().cloned(); }
+        if let Some(v) = a.strip_prefix(&prefix) { return Some(v.to_string()); }
+    }
+    None
+}
+
+// Numeric flag value; Ok(None) when absent, Err when present but unparsable
+pub fn parse_f64(args: &[String], flag: &str) -> Result<Option<f64>> {
+    match cli_value(args, flag) {
+        Some(v) => {
+            let x = v.parse::<f64>().with_context(|| format!("invalid {}: {}", flag, v))?;
+            if !x.is_finite() { anyhow::bail!("invalid {}: {}", flag, v); }
+            Ok(Some(x))
+        }
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,634 @@
+// Cut-and-analyze pipeline without mpv or the webview.
+// The live path uses the same window/naming helpers so clips line up either way.
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use anyhow::{Result, Context};
+use serde::Serialize;
+
+use crate::{analysis, cli, decode, dialogue, enhance, loudness, pitch, vad, wav};
+
+// Padding added before/after every subtitle window (seconds)
+pub const DEFAULT_PAD_S: f64 = 0.10;
+
+// Apply padding and clamp to [0, dur] (dur <= 0 means unknown)
+pub fn pad_window(start_s: f64, end_s: f64, dur: f64, pad: f64) -> (f64, f64) {
+    let s = if start_s > pad { start_s - pad } else { 0.0 };
+    let mut e = end_s + pad;
+    if dur > 0.0 && e > dur { e = dur; }
+    (s, e)
+}
+
+// mpv timing state that shifts the audio relative to sub-start/sub-end
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TimingCorrection {
+    // sub-delay: subtitles are shown this much later than their file timestamps
+    // (sub-start/sub-end report file timestamps, without the delay)
+    pub sub_delay: f64,
+    // audio-delay: audio is heard this much later than its stream timestamps
+    pub audio_delay: f64,
+    // Playback speed: media seconds per wall-clock second
+    pub speed: f64,
+}
+
+impl Default for TimingCorrection {
+    fn default() -> Self {
+        Self { sub_delay: 0.0, audio_delay: 0.0, speed: 1.0 }
+    }
+}
+
+impl TimingCorrection {
+    // Wall-clock time that `media_s` of playback takes at the current speed
+    pub fn wall_s(&self, media_s: f64) -> f64 {
+        media_s / self.speed.max(0.01)
+    }
+}
+
+// Padded window on the playback timeline (seek/pause) and in the audio stream (cut)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CorrectedWindow {
+    pub play_s: f64,
+    pub play_e: f64,
+    pub audio_s: f64,
+    pub audio_e: f64,
+}
+
+// The line is on screen at sub time + sub-delay; what is heard then is audio
+// stream time minus audio-delay. Both windows are padded and clamped to [0, dur].
+pub fn corrected_window(start_s: f64, end_s: f64, dur: f64, pad: f64, t: &TimingCorrection) -> CorrectedWindow {
+    let (play_s, play_e) = pad_window(start_s + t.sub_delay, end_s + t.sub_delay, dur, pad);
+    let shift = t.sub_delay - t.audio_delay;
+    let (audio_s, audio_e) = pad_window(start_s + shift, end_s + shift, dur, pad);
+    CorrectedWindow { play_s, play_e, audio_s, audio_e }
+}
+
+// `<basename>_<startms>_<endms>.wav` for an already padded window
+pub fn clip_path(out_dir: &Path, media_path: &str, s: f64, e: f64) -> PathBuf {
+    let base = Path::new(media_path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("clip");
+    let start_ms = (s * 1000.0).round() as u64;
+    let end_ms = (e * 1000.0).round() as u64;
+    out_dir.join(format!("{}_{}_{}.wav", base, start_ms, end_ms))
+}
+
+// Output directory used when none is given: ./shadow_out
+pub fn default_out_dir() -> PathBuf {
+    std::env::current_dir().unwrap_or_else(|_| std::env::temp_dir()).join("shadow_out")
+}
+
+// Chunk id of the JSON clip context embedded in every clip this app writes
+pub const CONTEXT_CHUNK_ID: [u8; 4] = *b"shdw";
+
+// What a clip was cut from; travels inside the WAV so copies keep their context
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipContext {
+    pub text: Option<String>,
+    pub translation: Option<String>,
+    pub media: String,
+    pub ff_index: Option<u64>,
+    // Padded window in the audio stream
+    pub s: f64,
+    pub e: f64,
+    // The line itself (unpadded), same timeline
+    pub line_s: f64,
+    pub line_e: f64,
+    // Set when the window was moved to the detected speech
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trim: Option<vad::Trim>,
+    // Source layout and how the dialogue was isolated, with --dialogue
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dialogue: Option<dialogue::Dialogue>,
+    pub f0: Option<analysis::F0Summary>,
+}
+
+impl ClipContext {
+    // LIST/INFO for generic tools (title = line, comment = source and window), a "line"
+    // region marker over the unpadded line (and a "speech" one when trimmed), plus the JSON chunk
+    pub fn to_metadata(&self, sample_rate: u32) -> wav::WavMetadata {
+        let media_name = Path::new(&self.media).file_name().and_then(|n| n.to_str()).unwrap_or(&self.media);
+        let mut meta = wav::WavMetadata::default();
+        if let Some(text) = &self.text {
+            meta.set_info(b"INAM", text.clone());
+        }
+        meta.set_info(b"ICMT", format!("{} {:.3}-{:.3}", media_name, self.s, self.e));
+        meta.set_info(b"ISFT", format!("shadow_analyzer {}", env!("CARGO_PKG_VERSION")));
+        let frame = |t: f64| ((t.clamp(self.s, self.e) - self.s) * sample_rate as f64).round() as u32;
+        let (start, end) = (frame(self.line_s), frame(self.line_e));
+        if end > start {
+            meta.cues.push(wav::CuePoint { id: 1, position: start, label: Some("line".to_string()), length: end - start });
+        }
+        if let Some(t) = &self.trim {
+            let (start, end) = (frame(t.speech_s), frame(t.speech_e));
+            if end > start {
+                meta.cues.push(wav::CuePoint { id: 2, position: start, label: Some("speech".to_string()), length: end - start });
+            }
+        }
+        meta.chunks.push((CONTEXT_CHUNK_ID, serde_json::to_vec(self).unwrap_or_default()));
+        meta
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CutRequest {
+    pub media_path: String,
+    // Unpadded window, as a subtitle would report it
+    pub start_s: f64,
+    pub end_s: f64,
+    // Container stream index (mpv's ff-index); None = first audio track
+    pub ff_index: Option<u64>,
+    pub pad_s: f64,
+    pub out_dir: PathBuf,
+    pub decoder: decode::Decoder,
+    // Sample format of the clip (and its normalised copy)
+    pub format: wav::SampleFormat,
+    // Line text and translation, embedded in the clip's metadata
+    pub text: Option<String>,
+    pub translation: Option<String>,
+    // Also write a `<clip>_norm.wav` copy at this loudness (LUFS)
+    pub normalize_lufs: Option<f32>,
+    // Move the window to the speech found near the line (the clip keeps its subtitle-based name)
+    pub auto_trim: bool,
+    // Clean the analysis input first and write it as `<clip>_enh.wav`
+    pub enhancer: Option<std::sync::Arc<dyn enhance::Enhancer>>,
+    // Analyse (and with Clip, save) the dialogue isolated from the mix
+    pub dialogue: dialogue::DialogueMode,
+    // Pitch estimator for the F0 summary
+    pub pitch: pitch::PitchMethod,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CutResult {
+    pub media: String,
+    pub start: f64,
+    pub end: f64,
+    // Padded window actually cut
+    pub s: f64,
+    pub e: f64,
+    // Subtitle and speech windows, with --auto-trim and speech found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trim: Option<vad::Trim>,
+    pub ff_index: Option<u64>,
+    pub out_path: String,
+    // Backend that decoded the window ("native" or "ffmpeg")
+    pub decoder: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dialogue: Option<dialogue::Dialogue>,
+    // Levels of the clip as written (the UI's RMS/Peak rows measure the same)
+    pub rms: f32,
+    pub peak: f32,
+    pub loudness: loudness::Loudness,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalization: Option<loudness::Normalization>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enhancement: Option<enhance::Enhancement>,
+    // F0 of the enhanced audio when an enhancer is set
+    pub f0: analysis::F0Summary,
+    pub elapsed_ms: u64,
+    // Optional stages that failed; the cut went on without them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+// Decode the window once; the analysis and then the clip (with its context) come from that buffer
+pub fn run_cut(req: &CutRequest) -> Result<CutResult> {
+    if !Path::new(&req.media_path).exists() {
+        anyhow::bail!("media not found: {}", req.media_path);
+    }
+    // Clamped to the end of the media like live mode, so both name a line's clip the same
+    let dur = decode::media_duration(req.decoder, &req.media_path, req.ff_index).unwrap_or(0.0);
+    let (s, e) = pad_window(req.start_s, req.end_s, dur, req.pad_s);
+    if s >= e {
+        anyhow::bail!("empty window {:.3}–{:.3}", s, e);
+    }
+    let started = Instant::now();
+    std::fs::create_dir_all(&req.out_dir)
+        .with_context(|| format!("create out dir: {}", req.out_dir.display()))?;
+    let out_path = clip_path(&req.out_dir, &req.media_path, s, e);
+    let mut warnings = Vec::new();
+    let (decode_s, decode_e) = if req.auto_trim { vad::search_window(s, e, dur) } else { (s, e) };
+    let decoded = decode::decode_window(req.decoder, &req.media_path, decode_s, decode_e, req.ff_index)?;
+    // Isolated over the whole decoded window, so auto-trim listens to the dialogue too.
+    // If that fails the full mix is used, as in live mode.
+    let (isolated, dialogue) = match req.dialogue {
+        dialogue::DialogueMode::Off => (None, None),
+        _ => match dialogue::isolate(&decoded) {
+            Ok((d, info)) => (Some(d), Some(info)),
+            Err(e) => {
+                eprintln!("dialogue isolation failed: {:#}", e);
+                warnings.push(format!("dialogue isolation failed: {:#}", e));
+                (None, None)
+            }
+        },
+    };
+    let (audio, isolated, trim) = if req.auto_trim {
+        let speech = vad::speech_near(isolated.as_ref().unwrap_or(&decoded), decode_s, (req.start_s, req.end_s));
+        let (audio, trim) = vad::trim_to(&decoded, decode_s, speech, (s, e), req.pad_s);
+        (audio, isolated.map(|d| vad::trim_to(&d, decode_s, speech, (s, e), req.pad_s).0), trim)
+    } else {
+        (decoded, isolated, None)
+    };
+    let (s, e) = trim.map(|t| (t.s, t.e)).unwrap_or((s, e));
+    // Analysis takes the dialogue when there is one; --dialogue clip saves it as well
+    let (audio, isolated) = match (req.dialogue, isolated) {
+        (dialogue::DialogueMode::Clip, Some(d)) => (d, None),
+        (_, d) => (audio, d),
+    };
+    let analysed = isolated.as_ref().unwrap_or(&audio);
+
+    let levels = loudness::measure_decoded(&audio);
+    let stereo = audio.to_stereo();
+    let (rms, peak) = analysis::rms_peak(&stereo);
+    let (mono, sr) = analysis::analysis_mono(&analysed.samples, analysed.channels, analysed.sample_rate);
+    // A failed enhancement falls back to the original audio, as in live mode
+    let enhanced = req.enhancer.as_deref().and_then(|en| match enhance::run(en, &mono, sr) {
+        Ok(x) => Some((en.name(), x)),
+        Err(e) => {
+            eprintln!("enhance failed: {:#}", e);
+            warnings.push(format!("enhance failed: {:#}", e));
+            None
+        }
+    });
+    let f0_input = enhanced.as_ref().map_or(&mono, |(_, (x, _))| x);
+    let f0 = analysis::summarize_f0(f0_input, sr, req.pad_s as f32, req.pitch);
+
+    let context = ClipContext {
+        text: req.text.clone(),
+        translation: req.translation.clone(),
+        media: req.media_path.clone(),
+        ff_index: req.ff_index,
+        s,
+        e,
+        line_s: req.start_s,
+        line_e: req.end_s,
+        trim,
+        dialogue: dialogue.clone(),
+        f0: Some(f0.clone()),
+    };
+    let meta = context.to_metadata(audio.sample_rate);
+    decode::write_clip(&audio, &out_path, req.format, &meta)?;
+    // The clip is on disk by now; a copy that cannot be written is a warning, as in live mode
+    let normalization = req.normalize_lufs.and_then(|target| {
+        let norm_path = loudness::normalized_path(&out_path);
+        let spec = wav::WavSpec { sample_rate: audio.sample_rate, channels: 2, format: req.format };
+        loudness::write_normalized(&norm_path, &stereo, spec, &levels, target, &meta)
+            .unwrap_or_else(|e| {
+                eprintln!("normalise failed: {:#}", e);
+                warnings.push(format!("normalise failed: {:#}", e));
+                None
+            })
+    });
+    let enhancement = enhanced.and_then(|(method, (x, ms))| {
+        let path = enhance::enhanced_path(&out_path);
+        enhance::write_enhanced(&path, &x, sr, method, ms, &context.to_metadata(sr))
+            .map_err(|e| {
+                eprintln!("enhance failed: {:#}", e);
+                warnings.push(format!("enhance failed: {:#}", e));
+            })
+            .ok()
+    });
+    Ok(CutResult {
+        media: req.media_path.clone(),
+        start: req.start_s,
+        end: req.end_s,
+        s,
+        e,
+        trim,
+        ff_index: req.ff_index,
+        out_path: out_path.to_string_lossy().to_string(),
+        decoder: audio.backend,
+        dialogue,
+        rms,
+        peak,
+        loudness: levels,
+        normalization,
+        enhancement,
+        f0,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+        warnings,
+    })
+}
+
+pub const CUT_USAGE: &str = "usage: shadow_analyzer cut --media <file> --start <sec> --end <sec> \
+[--track <ff-index>] [--pad <sec>] [--out-dir <dir>] [--decoder auto|native|ffmpeg] [--format pcm8|pcm16|pcm24|pcm32|float|double] [--normalize <LUFS>] [--auto-trim] [--enhance wiener|onnx [--enhance-model <file.onnx>] [--enhance-rate <Hz>]] [--dialogue analysis|clip] [--pitch mpm|yin|pyin]";
+
+// `shadow_analyzer cut ...`: prints one JSON object on stdout, returns the exit code
+pub fn run_cli(args: &[String]) -> i32 {
+    let req = match parse_cut_args(args) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("{:#}\n{}", e, CUT_USAGE);
+            return 2;
+        }
+    };
+    match run_cut(&req) {
+        Ok(res) => {
+            match serde_json::to_string(&res) {
+                Ok(js) => println!("{}", js),
+                Err(e) => { eprintln!("serialize result: {}", e); return 1; }
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("cut failed: {:#}", e);
+            1
+        }
+    }
+}
+
+fn parse_cut_args(args: &[String]) -> Result<CutRequest> {
+    let media_path = cli::cli_value(args, "--media").context("missing --media")?;
+    let start_s = cli::parse_f64(args, "--start")?.context("missing --start")?;
+    let end_s = cli::parse_f64(args, "--end")?.context("missing --end")?;
+    if end_s <= start_s {
+        anyhow::bail!("--end must be greater than --start");
+    }
+    let ff_index = match cli::cli_value(args, "--track") {
+        Some(v) => Some(v.parse::<u64>().with_context(|| format!("invalid --track: {}", v))?),
+        None => None,
+    };
+    let pad_s = cli::parse_f64(args, "--pad")?.unwrap_or(DEFAULT_PAD_S).max(0.0);
+    let out_dir = cli::cli_value(args, "--out-dir").map(PathBuf::from).unwrap_or_else(default_out_dir);
+    let decoder = decode::Decoder::resolve(cli::cli_value(args, "--decoder").as_deref())?;
+    let format = parse_format(args)?;
+    let normalize_lufs = loudness::parse_target(args)?;
+    let enhancer = enhance::parse_enhancer(args)?;
+    let dialogue = dialogue::DialogueMode::parse(args)?;
+    let pitch = pitch::parse_method(args)?;
+    Ok(CutRequest {
+        media_path,
+        start_s,
+        end_s,
+        ff_index,
+        pad_s,
+        out_dir,
+        decoder,
+        format,
+        text: None,
+        translation: None,
+        normalize_lufs,
+        auto_trim: args.iter().any(|a| a == "--auto-trim"),
+        enhancer,
+        dialogue,
+        pitch,
+    })
+}
+
+// `--format pcm8|pcm16|pcm24|pcm32|float|double` (default pcm16)
+pub fn parse_format(args: &[String]) -> Result<wav::SampleFormat> {
+    match cli::cli_value(args, "--format") {
+        Some(v) => wav::SampleFormat::parse(&v).with_context(|| format!("unknown --format '{}' (pcm8|pcm16|pcm24|pcm32|float|double)", v)),
+        None => Ok(wav::SampleFormat::Pcm16),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(v: &[&str]) -> Vec<String> { v.iter().map(|s| s.to_string()).collect() }
+
+    // Empty scratch directory for one test
+    fn scratch_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mpvshadow_cut_{}_{}", tag, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // Interleaved samples as a 16-bit 48 kHz WAV
+    fn write_media(path: &Path, channels: u16, samples: &[f32]) {
+        let spec = wav::WavSpec { sample_rate: 48000, channels, format: wav::SampleFormat::Pcm16 };
+        wav::write(path, samples, spec, &Default::default()).unwrap();
+    }
+
+    // 1 s of mono 200 Hz
+    fn tone_media(path: &Path) {
+        let tone: Vec<f32> = (0..48000).map(|i| 0.3 * (i as f32 * 200.0 * std::f32::consts::TAU / 48000.0).sin()).collect();
+        write_media(path, 1, &tone);
+    }
+
+    // Cut of 0.2-0.8 s of `media` with every option off
+    fn request(media: &Path, out_dir: &Path) -> CutRequest {
+        CutRequest {
+            media_path: media.to_string_lossy().to_string(),
+            start_s: 0.2,
+            end_s: 0.8,
+            ff_index: None,
+            pad_s: DEFAULT_PAD_S,
+            out_dir: out_dir.to_path_buf(),
+            decoder: decode::Decoder::Native,
+            format: wav::SampleFormat::Pcm16,
+            text: None,
+            translation: None,
+            normalize_lufs: None,
+            auto_trim: false,
+            enhancer: None,
+            dialogue: dialogue::DialogueMode::Off,
+            pitch: pitch::PitchMethod::Mpm,
+        }
+    }
+
+    // A written clip's reader (header and metadata) and its embedded context
+    fn read_clip(path: &str) -> (wav::WavReader, serde_json::Value) {
+        let reader = wav::WavReader::open(Path::new(path)).unwrap();
+        let (_, context) = reader.metadata.chunks.iter().find(|(id, _)| *id == CONTEXT_CHUNK_ID).expect("context chunk");
+        let context = serde_json::from_slice(context).unwrap();
+        (reader, context)
+    }
+
+    #[test]
+    fn test_pad_window_clamps() {
+        assert_eq!(pad_window(0.05, 1.0, 0.0, 0.10), (0.0, 1.1));
+        let (s, e) = pad_window(2.0, 9.95, 10.0, 0.10);
+        assert!((s - 1.9).abs() < 1e-9 && e == 10.0);
+    }
+
+    #[test]
+    fn test_corrected_window_delays() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        let w = corrected_window(10.0, 12.0, 0.0, 0.10, &TimingCorrection::default());
+        assert!(close(w.play_s, 9.9) && close(w.audio_s, 9.9) && close(w.audio_e, 12.1));
+        // Subs shown 0.5 s late, audio heard 0.2 s late
+        let t = TimingCorrection { sub_delay: 0.5, audio_delay: 0.2, speed: 1.5 };
+        let w = corrected_window(10.0, 12.0, 0.0, 0.10, &t);
+        assert!(close(w.play_s, 10.4) && close(w.play_e, 12.6));
+        assert!(close(w.audio_s, 10.2) && close(w.audio_e, 12.4));
+        assert!(close(t.wall_s(w.play_e - w.play_s), 2.2 / 1.5));
+    }
+
+    #[test]
+    fn test_clip_path_naming() {
+        let p = clip_path(Path::new("out"), "/media/ep01.mkv", 12.2, 15.9);
+        assert_eq!(p, Path::new("out").join("ep01_12200_15900.wav"));
+    }
+
+    #[test]
+    fn test_parse_cut_args() {
+        let req = parse_cut_args(&args(&[
+            "--media", "ep01.mkv", "--start", "12.3", "--end=15.8", "--track", "2",
+        ])).unwrap();
+        assert_eq!(req.media_path, "ep01.mkv");
+        assert_eq!(req.ff_index, Some(2));
+        assert!((req.end_s - 15.8).abs() < 1e-9);
+        assert_eq!(req.pad_s, DEFAULT_PAD_S);
+        assert!(parse_cut_args(&args(&["--media", "x", "--start", "3", "--end", "1"])).is_err());
+        assert!(parse_cut_args(&args(&["--start", "1", "--end", "2"])).is_err());
+        assert!(parse_cut_args(&args(&["--media", "x", "--start", "1", "--end", "2", "--format", "s12"])).is_err());
+    }
+
+    #[test]
+    fn test_run_cut_clamps_to_the_end_of_the_media() {
+        let dir = scratch_dir("end");
+        let media = dir.join("tone.wav");
+        tone_media(&media);
+        // Last line of the 1 s file: the padded end stops at 1.0 s, as in live mode
+        let req = CutRequest { start_s: 0.5, end_s: 1.0, ..request(&media, &dir) };
+        let res = run_cut(&req).unwrap();
+        assert!((res.s - 0.4).abs() < 1e-9 && res.e == 1.0, "{:?}", (res.s, res.e));
+        assert!(res.out_path.ends_with("tone_400_1000.wav"), "{}", res.out_path);
+        let (reader, _) = read_clip(&res.out_path);
+        assert_eq!(reader.frames(), 28800);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_run_cut_embeds_context() {
+        let dir = scratch_dir("ctx");
+        let media = dir.join("tone.wav");
+        tone_media(&media);
+        let req = CutRequest {
+            format: wav::SampleFormat::Pcm24,
+            text: Some("テスト".to_string()),
+            translation: Some("test".to_string()),
+            enhancer: Some(std::sync::Arc::new(enhance::Wiener)),
+            ..request(&media, &dir)
+        };
+        let res = run_cut(&req).unwrap();
+        let (mut reader, ctx) = read_clip(&res.out_path);
+        assert_eq!((reader.info.format, reader.info.channels), (wav::SampleFormat::Pcm24, 2));
+        let mut clip = Vec::new();
+        reader.read_frames(&mut clip, reader.frames() as usize).unwrap();
+        let (rms, peak) = analysis::rms_peak(&clip);
+        assert!((res.rms - rms).abs() < 1e-4 && (res.peak - peak).abs() < 1e-4, "{:?}", (res.rms, res.peak, rms, peak));
+        assert_eq!(ctx["text"], "テスト");
+        assert_eq!(ctx["translation"], "test");
+        assert!((ctx["e"].as_f64().unwrap() - 0.9).abs() < 1e-9);
+        assert!((ctx["f0"]["median_hz"].as_f64().unwrap() - 200.0).abs() < 5.0);
+        assert_eq!(reader.metadata.info(b"INAM"), Some("テスト"));
+        // The line marker covers 0.2-0.8 s of the media, i.e. after the 0.1 s of padding
+        let line = &reader.metadata.cues[0];
+        assert_eq!(line.label.as_deref(), Some("line"));
+        assert_eq!((line.position, line.length), (4800, 28800));
+        // The enhanced copy sits next to the clip: mono at the analysis rate, same markers
+        let enh = res.enhancement.expect("enhancement");
+        assert_eq!(enh.method, "wiener");
+        let (reader, _) = read_clip(&enh.path);
+        assert_eq!((reader.info.sample_rate, reader.info.channels), (analysis::ANALYSIS_SAMPLE_RATE, 1));
+        assert!(reader.metadata.info(b"ICMT").is_some_and(|c| c.ends_with("enhanced (wiener)")));
+        assert_eq!(reader.metadata.cues[0].position, 2400);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_run_cut_enhancement_failure_falls_back() {
+        let dir = scratch_dir("enh_fail");
+        let media = dir.join("tone.wav");
+        tone_media(&media);
+        let req = CutRequest { enhancer: Some(std::sync::Arc::new(enhance::FailingEnhancer)), ..request(&media, &dir) };
+        // The clip and the F0 of the original audio are still there
+        let res = run_cut(&req).unwrap();
+        assert!(res.enhancement.is_none());
+        assert_eq!(res.warnings.len(), 1);
+        assert!(res.warnings[0].contains("model error"), "{:?}", res.warnings);
+        assert!((res.f0.median_hz.unwrap() - 200.0).abs() < 5.0);
+        assert!(Path::new(&res.out_path).exists());
+        assert!(!enhance::enhanced_path(Path::new(&res.out_path)).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_run_cut_keeps_the_clip_when_the_enhanced_copy_fails() {
+        let dir = scratch_dir("enh_write");
+        let media = dir.join("tone.wav");
+        tone_media(&media);
+        // A directory where the enhanced copy goes: the rename onto it fails
+        let clip = clip_path(&dir, &media.to_string_lossy(), 0.1, 0.9);
+        std::fs::create_dir_all(enhance::enhanced_path(&clip)).unwrap();
+        let req = CutRequest { enhancer: Some(std::sync::Arc::new(enhance::Wiener)), ..request(&media, &dir) };
+        let res = run_cut(&req).unwrap();
+        assert!(res.enhancement.is_none());
+        assert_eq!(res.warnings.len(), 1);
+        assert!(res.warnings[0].starts_with("enhance failed"), "{:?}", res.warnings);
+        assert!(Path::new(&res.out_path).exists() && res.f0.median_hz.is_some());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_run_cut_keeps_the_clip_when_the_normalised_copy_fails() {
+        let dir = scratch_dir("norm_write");
+        let media = dir.join("tone.wav");
+        tone_media(&media);
+        let clip = clip_path(&dir, &media.to_string_lossy(), 0.1, 0.9);
+        std::fs::create_dir_all(loudness::normalized_path(&clip)).unwrap();
+        let res = run_cut(&CutRequest { normalize_lufs: Some(-23.0), ..request(&media, &dir) }).unwrap();
+        assert!(res.normalization.is_none());
+        assert_eq!(res.warnings.len(), 1);
+        assert!(res.warnings[0].starts_with("normalise failed"), "{:?}", res.warnings);
+        assert!(Path::new(&res.out_path).exists() && res.loudness.integrated_lufs.is_some());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_run_cut_dialogue_clip() {
+        let dir = scratch_dir("dialogue");
+        let media = dir.join("film.wav");
+        // 200 Hz in the centre, a louder 330 Hz only on the left
+        let sine = |hz: f32, i: usize| (i as f32 * hz * std::f32::consts::TAU / 48000.0).sin();
+        let pcm: Vec<f32> = (0..48000).flat_map(|i| [0.3 * sine(200.0, i) + 0.5 * sine(330.0, i), 0.3 * sine(200.0, i)]).collect();
+        write_media(&media, 2, &pcm);
+        let req = CutRequest {
+            format: wav::SampleFormat::Float32,
+            dialogue: dialogue::DialogueMode::Clip,
+            pitch: pitch::PitchMethod::Pyin,
+            ..request(&media, &dir)
+        };
+        let res = run_cut(&req).unwrap();
+        let d = res.dialogue.expect("dialogue");
+        assert_eq!((d.layout.as_str(), d.method), ("stereo", "coherence"));
+        assert!((res.f0.median_hz.unwrap() - 200.0).abs() < 5.0, "{:?}", res.f0.median_hz);
+        // The clip is the dialogue on both sides, with the layout noted in its context
+        let (mut reader, ctx) = read_clip(&res.out_path);
+        let mut clip = Vec::new();
+        reader.read_frames(&mut clip, reader.frames() as usize).unwrap();
+        assert!(clip.chunks_exact(2).all(|f| f[0] == f[1]));
+        assert_eq!(ctx["dialogue"]["method"], "coherence");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_run_cut_auto_trim() {
+        let dir = scratch_dir("trim");
+        let media = dir.join("late.wav");
+        // Speech-band tone at 1.3-2.1 s of 3 s; the subtitle says 1.0-1.8
+        let tone: Vec<f32> = (0..144000).map(|i| {
+            let t = i as f32 / 48000.0;
+            if (1.3..2.1).contains(&t) { 0.3 * (t * 220.0 * std::f32::consts::TAU).sin() } else { 0.0 }
+        }).collect();
+        write_media(&media, 1, &tone);
+        let req = CutRequest { start_s: 1.0, end_s: 1.8, auto_trim: true, ..request(&media, &dir) };
+        let res = run_cut(&req).unwrap();
+        let t = res.trim.expect("trim");
+        assert!((t.speech_s - 1.3).abs() < 0.03 && (t.speech_e - 2.1).abs() < 0.03, "{:?}", t);
+        assert!((res.s - (t.speech_s - 0.1)).abs() < 1e-9 && (res.e - (t.speech_e + 0.1)).abs() < 1e-9);
+        assert!(res.out_path.ends_with("late_900_1900.wav"), "{}", res.out_path);
+        let (reader, _) = read_clip(&res.out_path);
+        assert!((reader.frames() as f64 / 48000.0 - (res.e - res.s)).abs() < 1e-3);
+        let cues = &reader.metadata.cues;
+        let labels: Vec<_> = cues.iter().map(|m| m.label.as_deref().unwrap_or("")).collect();
+        assert_eq!(labels, vec!["line", "speech"]);
+        assert!((cues[1].position as f64 / 48000.0 - 0.1).abs() < 0.001);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
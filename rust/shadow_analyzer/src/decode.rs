@@ -0,0 +1,395 @@
+// Audio decoding for a cut window: in-process (symphonia) with the external ffmpeg
+// pipeline as a fallback backend.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use anyhow::{Result, Context};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, SeekMode, SeekTo, Track};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::units::Time;
+
+use crate::{ffmpeg, wav};
+
+// Environment variable that picks the backend when no --decoder flag is given
+pub const DECODER_ENV: &str = "MPVSHADOW_DECODER";
+
+// Decoded from this far before the window so codecs with overlap (AAC, Vorbis) have settled
+const PREROLL_S: f64 = 0.2;
+
+// Speaker bits (WAVE_FORMAT_EXTENSIBLE order, as symphonia uses)
+pub const SPEAKER_FRONT_LEFT: u32 = 0x1;
+pub const SPEAKER_FRONT_RIGHT: u32 = 0x2;
+pub const SPEAKER_FRONT_CENTER: u32 = 0x4;
+pub const SPEAKER_LOW_FREQUENCY: u32 = 0x8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Decoder {
+    // Native first, ffmpeg if the native stack cannot handle the file
+    #[default]
+    Auto,
+    Native,
+    Ffmpeg,
+}
+
+impl Decoder {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Some(Self::Auto),
+            "native" => Some(Self::Native),
+            "ffmpeg" => Some(Self::Ffmpeg),
+            _ => None,
+        }
+    }
+
+    // `--decoder` value, else MPVSHADOW_DECODER, else auto
+    pub fn resolve(cli_value: Option<&str>) -> Result<Self> {
+        let env = std::env::var(DECODER_ENV).ok();
+        match cli_value.or(env.as_deref()) {
+            Some(v) => Self::parse(v).with_context(|| format!("unknown decoder '{}' (auto|native|ffmpeg)", v)),
+            None => Ok(Self::Auto),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DecodedAudio {
+    // Interleaved, [-1, 1]
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub channels: u16,
+    // Speaker mask of the channels in order (0 = unknown)
+    pub channel_mask: u32,
+    // "native" or "ffmpeg"
+    pub backend: &'static str,
+}
+
+impl DecodedAudio {
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    // Interleaved stereo: mono is duplicated, surround is folded down ffmpeg-style
+    // (centre and surrounds at -3 dB, LFE dropped)
+    pub fn to_stereo(&self) -> Vec<f32> {
+        let ch = self.channels.max(1) as usize;
+        if ch == 2 { return self.samples.clone(); }
+        let mut out = Vec::with_capacity(self.frames() * 2);
+        if ch == 1 {
+            for &x in &self.samples { out.push(x); out.push(x); }
+            return out;
+        }
+        let roles = self.channel_roles();
+        let g = std::f32::consts::FRAC_1_SQRT_2;
+        let mut norm = [0f32; 2];
+        let weights: Vec<(f32, f32)> = roles.iter().map(|&r| match r {
+            SPEAKER_FRONT_LEFT => (1.0, 0.0),
+            SPEAKER_FRONT_RIGHT => (0.0, 1.0),
+            SPEAKER_FRONT_CENTER => (g, g),
+            SPEAKER_LOW_FREQUENCY => (0.0, 0.0),
+            // Left-side speakers have the lower bit of each left/right pair
+            r if is_left(r) => (g, 0.0),
+            _ => (0.0, g),
+        }).collect();
+        for w in &weights { norm[0] += w.0; norm[1] += w.1; }
+        for frame in self.samples.chunks_exact(ch) {
+            let (mut l, mut r) = (0f32, 0f32);
+            for (x, w) in frame.iter().zip(&weights) { l += x * w.0; r += x * w.1; }
+            out.push(l / norm[0].max(1.0));
+            out.push(r / norm[1].max(1.0));
+        }
+        out
+    }
+
+    // Frames from `from_s` to `to_s` seconds into the buffer (clamped)
+    pub fn slice(&self, from_s: f64, to_s: f64) -> DecodedAudio {
+        let ch = self.channels.max(1) as usize;
+        let frame = |t: f64| ((t.max(0.0) * self.sample_rate as f64).round() as usize).min(self.frames());
+        let (a, b) = (frame(from_s), frame(to_s));
+        DecodedAudio {
+            samples: self.samples[a * ch..b.max(a) * ch].to_vec(),
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            channel_mask: self.channel_mask,
+            backend: self.backend,
+        }
+    }
+
+    // Speaker bit per channel; unknown layouts are treated as FL, FR, FC, LFE, ...
+    pub fn channel_roles(&self) -> Vec<u32> {
+        let mut roles = Vec::with_capacity(self.channels as usize);
+        let mut mask = self.channel_mask;
+        for i in 0..self.channels as u32 {
+            if mask != 0 {
+                let bit = mask & mask.wrapping_neg();
+                roles.push(bit);
+                mask &= !bit;
+            } else {
+                roles.push(1u32 << i.min(31));
+            }
+        }
+        roles
+    }
+}
+
+// Back/side/top left speakers sit on even bit positions after FL, FR, FC, LFE
+fn is_left(bit: u32) -> bool {
+    const LEFTS: u32 = 0x10 | 0x40 | 0x200 | 0x1000 | 0x8000;
+    bit & LEFTS != 0
+}
+
+// Decode [start_s, end_s) of the given ffmpeg stream index (None = first audio track)
+pub fn decode_window(decoder: Decoder, media_path: &str, start_s: f64, end_s: f64, ff_index: Option<u64>) -> Result<DecodedAudio> {
+    match decoder {
+        Decoder::Native => decode_native(media_path, start_s, end_s, ff_index),
+        Decoder::Ffmpeg => decode_ffmpeg(media_path, start_s, end_s, ff_index),
+        Decoder::Auto => decode_native(media_path, start_s, end_s, ff_index).or_else(|e| {
+            eprintln!("native decode failed ({:#}); falling back to ffmpeg", e);
+            decode_ffmpeg(media_path, start_s, end_s, ff_index)
+                .map_err(|fe| fe.context(format!("native decode also failed: {:#}", e)))
+        }),
+    }
+}
+
+// Length of the media in seconds (the chosen audio track, else the container), None when
+// neither backend can tell
+pub fn media_duration(decoder: Decoder, media_path: &str, ff_index: Option<u64>) -> Option<f64> {
+    match decoder {
+        Decoder::Native => duration_native(media_path, ff_index).ok(),
+        Decoder::Ffmpeg => ffmpeg::probe_duration(media_path).ok(),
+        Decoder::Auto => duration_native(media_path, ff_index).or_else(|_| ffmpeg::probe_duration(media_path)).ok(),
+    }
+}
+
+// Clip file for a decoded window: stereo at the decoded rate
+pub fn write_clip(audio: &DecodedAudio, out_path: &Path, format: wav::SampleFormat, meta: &wav::WavMetadata) -> Result<()> {
+    let spec = wav::WavSpec { sample_rate: audio.sample_rate, channels: 2, format };
+    wav::write(out_path, &audio.to_stereo(), spec, meta)
+}
+
+// mpv's ff-index is the container stream index, which is also symphonia's track order.
+// Without one, take the first decodable audio track. A requested track symphonia cannot
+// decode (AC-3, DTS, TrueHD in MKV) is an error rather than another language's track,
+// so Decoder::Auto retries it with ffmpeg.
+fn pick_track(tracks: &[Track], ff_index: Option<u64>) -> Result<&Track> {
+    let is_audio = |t: &&Track| t.codec_params.codec != CODEC_TYPE_NULL && t.codec_params.sample_rate.is_some();
+    match ff_index {
+        Some(i) => tracks.get(i as usize)
+            .filter(is_audio)
+            .with_context(|| format!("track {} is not a decodable audio track", i)),
+        None => tracks.iter().find(is_audio).context("no decodable audio track"),
+    }
+}
+
+// Open the container and probe its format
+fn probe_native(media_path: &str) -> Result<Box<dyn symphonia::core::formats::FormatReader>> {
+    let file = File::open(media_path).with_context(|| format!("open media: {}", media_path))?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = Path::new(media_path).extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let format_opts = FormatOptions { enable_gapless: true, ..Default::default() };
+    let probed = symphonia::default::get_probe()
+        .format(&hint, mss, &format_opts, &MetadataOptions::default())
+        .context("unsupported container")?;
+    Ok(probed.format)
+}
+
+// Frame count of the track from its header (WAV, FLAC, MP4 and MKV with a duration have one)
+fn duration_native(media_path: &str, ff_index: Option<u64>) -> Result<f64> {
+    let format = probe_native(media_path)?;
+    let params = &pick_track(format.tracks(), ff_index)?.codec_params;
+    let n_frames = params.n_frames.context("track without a frame count")?;
+    let sample_rate = params.sample_rate.context("audio track without sample rate")?;
+    Ok(match params.time_base {
+        Some(tb) => { let t = tb.calc_time(n_frames); t.seconds as f64 + t.frac }
+        None => n_frames as f64 / sample_rate as f64,
+    })
+}
+
+fn decode_native(media_path: &str, start_s: f64, end_s: f64, ff_index: Option<u64>) -> Result<DecodedAudio> {
+    let mut format = probe_native(media_path)?;
+    let track = pick_track(format.tracks(), ff_index)?;
+    let track_id = track.id;
+    let params = track.codec_params.clone();
+    let sample_rate = params.sample_rate.context("audio track without sample rate")?;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&params, &DecoderOptions::default())
+        .context("unsupported audio codec")?;
+    let ts_to_s = |ts: u64| match params.time_base {
+        Some(tb) => { let t = tb.calc_time(ts); t.seconds as f64 + t.frac }
+        None => ts as f64 / sample_rate as f64,
+    };
+
+    let seek_s = (start_s - PREROLL_S).max(0.0);
+    if seek_s > 0.0 {
+        let to = SeekTo::Time { time: Time::from(seek_s), track_id: Some(track_id) };
+        format.seek(SeekMode::Accurate, to).context("seek failed")?;
+        decoder.reset();
+    }
+
+    let mut out: Vec<f32> = Vec::new();
+    let mut channels: u16 = 0;
+    let mut channel_mask: u32 = 0;
+    let mut sample_buf: Option<SampleBuffer<f32>> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(p) => p,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e).context("demux error"),
+        };
+        if packet.track_id() != track_id { continue; }
+        let pkt_s = ts_to_s(packet.ts);
+        if pkt_s >= end_s { break; }
+        let decoded = match decoder.decode(&packet) {
+            Ok(d) => d,
+            // A corrupt packet is skipped, like ffmpeg does
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(e).context("decode error"),
+        };
+        let spec = *decoded.spec();
+        let ch = spec.channels.count().max(1);
+        channels = ch as u16;
+        channel_mask = spec.channels.bits();
+        let buf = match sample_buf.as_mut() {
+            Some(b) if b.capacity() >= decoded.capacity() * ch => b,
+            _ => sample_buf.insert(SampleBuffer::<f32>::new(decoded.capacity() as u64, spec)),
+        };
+        buf.copy_interleaved_ref(decoded);
+        let samples = buf.samples();
+        let frames = samples.len() / ch;
+        let first = (packet.trim_start as usize).min(frames);
+        let last = frames.saturating_sub(packet.trim_end as usize).max(first);
+        for f in first..last {
+            let t = pkt_s + (f - first) as f64 / sample_rate as f64;
+            if t < start_s { continue; }
+            if t >= end_s { break; }
+            out.extend_from_slice(&samples[f * ch..(f + 1) * ch]);
+        }
+    }
+    if channels == 0 {
+        anyhow::bail!("no audio decoded in {:.3}-{:.3}", start_s, end_s);
+    }
+    Ok(DecodedAudio { samples: out, sample_rate, channels, channel_mask, backend: "native" })
+}
+
+// Whole window through `ffmpeg ... -f wav -c:a pcm_f32le -ar 48000 pipe:1`, in the source layout
+fn decode_ffmpeg(media_path: &str, start_s: f64, end_s: f64, ff_index: Option<u64>) -> Result<DecodedAudio> {
+    let base_args = ffmpeg::build_ffmpeg_base_args(media_path, start_s, end_s, ff_index);
+    let (mut child, mut stdout) = ffmpeg::spawn_wav_pipe(&base_args)?;
+    let mut bytes = Vec::new();
+    let read = stdout.read_to_end(&mut bytes);
+    let status = child.wait().context("ffmpeg wav wait")?;
+    read.context("ffmpeg wav read")?;
+    if !status.success() {
+        anyhow::bail!("ffmpeg wav exited with status {:?}", status.code());
+    }
+    let (info, samples) = wav::read_wav_bytes(&bytes).context("ffmpeg wav output")?;
+    Ok(DecodedAudio {
+        samples,
+        sample_rate: info.sample_rate,
+        channels: info.channels,
+        // Plain (non-extensible) headers are mono or stereo in the default order
+        channel_mask: info.channel_mask,
+        backend: "ffmpeg",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone_file(name: &str, sr: u32, secs: f32) -> std::path::PathBuf {
+        let mut p = std::env::temp_dir();
+        p.push(format!("mpvshadow_decode_{}_{}.wav", name, std::process::id()));
+        // Left: ramp of sample index (to check the cut position), right: silence
+        let n = (sr as f32 * secs) as usize;
+        let mut pcm = Vec::with_capacity(n * 2);
+        for i in 0..n {
+            pcm.push((i % 1000) as f32 / 1000.0);
+            pcm.push(0.0);
+        }
+        let spec = wav::WavSpec { sample_rate: sr, channels: 2, format: wav::SampleFormat::Pcm16 };
+        wav::write(&p, &pcm, spec, &Default::default()).unwrap();
+        p
+    }
+
+    #[test]
+    fn test_native_decode_window_is_sample_accurate() {
+        let p = tone_file("window", 8000, 2.0);
+        let a = decode_window(Decoder::Native, p.to_str().unwrap(), 0.5, 1.25, None).unwrap();
+        assert_eq!((a.sample_rate, a.channels, a.backend), (8000, 2, "native"));
+        assert_eq!(a.frames(), 6000);
+        // First frame is sample 4000 of the ramp: 4000 % 1000 == 0, then 1/1000 steps
+        assert!(a.samples[0].abs() < 1e-3 && (a.samples[2] - 0.001).abs() < 1e-3);
+        let _ = std::fs::remove_file(&p);
+    }
+
+    #[test]
+    fn test_write_clip_roundtrip() {
+        let p = tone_file("roundtrip", 16000, 1.0);
+        let mut out = std::env::temp_dir();
+        out.push(format!("mpvshadow_decode_out_{}.wav", std::process::id()));
+        let audio = decode_window(Decoder::Native, p.to_str().unwrap(), 0.25, 0.75, None).unwrap();
+        write_clip(&audio, &out, wav::SampleFormat::Pcm16, &Default::default()).unwrap();
+        let (mono, sr) = wav::read_wav_mono(&out, None).unwrap();
+        assert_eq!((mono.len(), sr), (8000, 16000));
+        let _ = std::fs::remove_file(&p);
+        let _ = std::fs::remove_file(&out);
+    }
+
+    #[test]
+    fn test_media_duration() {
+        let p = tone_file("duration", 8000, 1.5);
+        let dur = media_duration(Decoder::Native, p.to_str().unwrap(), None).unwrap();
+        assert!((dur - 1.5).abs() < 1e-9, "{}", dur);
+        assert_eq!(media_duration(Decoder::Native, "/nonexistent/media.mkv", None), None);
+        let _ = std::fs::remove_file(&p);
+    }
+
+    #[test]
+    fn test_to_stereo_folds_surround() {
+        // 5.1: FL FR FC LFE BL BR, centre only
+        let a = DecodedAudio {
+            samples: vec![0.0, 0.0, 1.0, 1.0, 0.0, 0.0],
+            sample_rate: 48000,
+            channels: 6,
+            channel_mask: 0x3f,
+            backend: "native",
+        };
+        let st = a.to_stereo();
+        assert_eq!(st.len(), 2);
+        assert!((st[0] - st[1]).abs() < 1e-6 && st[0] > 0.2);
+        let m = DecodedAudio { samples: vec![0.5], channels: 1, channel_mask: 0, ..a };
+        assert_eq!(m.to_stereo(), vec![0.5, 0.5]);
+    }
+
+    #[test]
+    fn test_pick_track_keeps_the_requested_index() {
+        use symphonia::core::codecs::{CodecParameters, CODEC_TYPE_AAC};
+        // 0: video, 1: AC-3 (no symphonia decoder, so CODEC_TYPE_NULL), 2: AAC
+        let aac = CodecParameters::new().for_codec(CODEC_TYPE_AAC).with_sample_rate(48000).clone();
+        let tracks = vec![
+            Track::new(1, CodecParameters::new()),
+            Track::new(2, CodecParameters::new().with_sample_rate(48000).clone()),
+            Track::new(3, aac),
+        ];
+        assert_eq!(pick_track(&tracks, None).unwrap().id, 3);
+        assert_eq!(pick_track(&tracks, Some(2)).unwrap().id, 3);
+        assert!(pick_track(&tracks, Some(1)).is_err());
+        assert!(pick_track(&tracks, Some(5)).is_err());
+    }
+
+    #[test]
+    fn test_decoder_parse() {
+        assert_eq!(Decoder::parse("FFmpeg"), Some(Decoder::Ffmpeg));
+        assert_eq!(Decoder::resolve(Some("native")).unwrap(), Decoder::Native);
+        assert!(Decoder::resolve(Some("gstreamer")).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+// Dialogue isolation: films mix dialogue to the centre channel and spread music and effects
+// over the others, so speech detection and F0 (and optionally the clip) can take the centre
+// alone. Stereo has no centre channel; it is extracted from what both sides share.
+
+use anyhow::Result;
+use realfft::num_complex::Complex32;
+use serde::Serialize;
+
+use crate::cli;
+use crate::decode::{DecodedAudio, SPEAKER_FRONT_CENTER, SPEAKER_FRONT_LEFT, SPEAKER_FRONT_RIGHT, SPEAKER_LOW_FREQUENCY};
+use crate::stft::Stft;
+
+// What the isolated dialogue is used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DialogueMode {
+    // The full mix everywhere
+    #[default]
+    Off,
+    // Speech detection and F0 use the dialogue, the clip keeps the full mix
+    Analysis,
+    // The clip (and its levels) is the dialogue as well
+    Clip,
+}
+
+impl DialogueMode {
+    // `--dialogue analysis|clip`; Off when not given
+    pub fn parse(args: &[String]) -> Result<Self> {
+        match cli::cli_value(args, "--dialogue").as_deref() {
+            None => Ok(Self::Off),
+            Some("analysis") => Ok(Self::Analysis),
+            Some("clip") => Ok(Self::Clip),
+            Some(v) => anyhow::bail!("unknown --dialogue '{}' (analysis|clip)", v),
+        }
+    }
+}
+
+// Source layout and where the dialogue came from, reported with the cut
+#[derive(Debug, Clone, Serialize)]
+pub struct Dialogue {
+    // "mono", "stereo", "5.1", ...
+    pub layout: String,
+    // "centre" (its own channel), "coherence" (extracted from the front pair), "mono" or "mix"
+    pub method: &'static str,
+}
+
+// STFT frame of the stereo extraction
+const FRAME_S: f64 = 0.032;
+// Recursive smoothing of the per-bin auto and cross spectra (about 10 frames)
+const SMOOTH: f32 = 0.9;
+// Exponent on the inter-channel similarity: higher passes less of what is only partly shared
+const SHARPNESS: i32 = 2;
+
+// "mono", "stereo", or full-range.LFE channel counts for surround ("5.1", "7.1", "4.0")
+pub fn layout_name(audio: &DecodedAudio) -> String {
+    match audio.channels {
+        0 | 1 => "mono".to_string(),
+        2 => "stereo".to_string(),
+        n => {
+            let lfe = audio.channel_roles().iter().filter(|&&r| r == SPEAKER_LOW_FREQUENCY).count();
+            format!("{}.{}", n as usize - lfe, lfe)
+        }
+    }
+}
+
+// Mono dialogue at the same rate: the centre channel when the layout has one, else the
+// centre extracted from the front left/right pair
+pub fn isolate(audio: &DecodedAudio) -> Result<(DecodedAudio, Dialogue)> {
+    let ch = audio.channels.max(1) as usize;
+    let roles = audio.channel_roles();
+    let channel = |role: u32| roles.iter().position(|&r| r == role)
+        .map(|i| audio.samples.iter().skip(i).step_by(ch).copied().collect::<Vec<f32>>());
+    let (samples, method) = if ch == 1 {
+        (audio.samples.clone(), "mono")
+    } else if let Some(centre) = channel(SPEAKER_FRONT_CENTER) {
+        (centre, "centre")
+    } else if let (Some(l), Some(r)) = (channel(SPEAKER_FRONT_LEFT), channel(SPEAKER_FRONT_RIGHT)) {
+        (extract_centre(&l, &r, audio.sample_rate)?, "coherence")
+    } else {
+        (audio.samples.chunks_exact(ch).map(|f| f.iter().sum::<f32>() / ch as f32).collect(), "mix")
+    };
+    let dialogue = Dialogue { layout: layout_name(audio), method };
+    let isolated = DecodedAudio {
+        samples,
+        sample_rate: audio.sample_rate,
+        channels: 1,
+        channel_mask: SPEAKER_FRONT_CENTER,
+        backend: audio.backend,
+    };
+    Ok((isolated, dialogue))
+}
+
+// Mid signal weighted per bin by the inter-channel similarity 2|Φlr| / (Φll + Φrr)
+// (Avendano & Jot): 1 for what is panned to the centre, near 0 for what is on one side
+// only or differs between the sides (wide reverb, decorrelated music)
+fn extract_centre(l: &[f32], r: &[f32], sample_rate: u32) -> Result<Vec<f32>> {
+    let stft = Stft::new(FRAME_S, sample_rate);
+    let (left, right) = (stft.analyze(l)?, stft.analyze(r)?);
+    let bins = stft.bins();
+    let (mut pll, mut prr) = (vec![0.0f32; bins], vec![0.0f32; bins]);
+    let mut plr = vec![Complex32::new(0.0, 0.0); bins];
+    let mut mids = Vec::with_capacity(left.len());
+    for (mut lf, rf) in left.into_iter().zip(right) {
+        for k in 0..bins {
+            let (a, b) = (lf[k], rf[k]);
+            pll[k] = SMOOTH * pll[k] + (1.0 - SMOOTH) * a.norm_sqr();
+            prr[k] = SMOOTH * prr[k] + (1.0 - SMOOTH) * b.norm_sqr();
+            plr[k] = plr[k] * SMOOTH + a * b.conj() * (1.0 - SMOOTH);
+            let similarity = (2.0 * plr[k].norm() / (pll[k] + prr[k]).max(1e-12)).min(1.0);
+            lf[k] = (a + b) * 0.5 * similarity.powi(SHARPNESS);
+        }
+        mids.push(lf);
+    }
+    stft.synthesize(mids, l.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{tone, voice};
+
+    const SR: u32 = 24000;
+
+    fn audio(channels: u16, channel_mask: u32, planes: &[&[f32]]) -> DecodedAudio {
+        let n = planes[0].len();
+        let samples = (0..n).flat_map(|i| planes.iter().map(move |p| p[i])).collect();
+        DecodedAudio { samples, sample_rate: SR, channels, channel_mask, backend: "native" }
+    }
+
+    // Energy of a - b over the middle, relative to b, in dB
+    fn error_db(a: &[f32], b: &[f32]) -> f32 {
+        let mid = b.len() / 4..3 * b.len() / 4;
+        let err: f32 = mid.clone().map(|i| (a[i] - b[i]).powi(2)).sum();
+        let sig: f32 = mid.map(|i| b[i].powi(2)).sum();
+        10.0 * (err / sig).log10()
+    }
+
+    #[test]
+    fn test_surround_takes_the_centre_channel() {
+        let n = SR as usize / 2;
+        let (v, music) = (voice(SR, n), tone(SR, 440.0, 0.8, n));
+        let silent = vec![0.0; n];
+        // FL FR FC LFE BL BR
+        let a = audio(6, 0x3f, &[&music, &music, &v, &music, &music, &music]);
+        let (d, info) = isolate(&a).unwrap();
+        assert_eq!((info.layout.as_str(), info.method, d.channels), ("5.1", "centre", 1));
+        assert_eq!(d.samples, v);
+        // Without a mask the order is taken as FL FR FC LFE ...
+        let a = audio(4, 0, &[&music, &music, &v, &silent]);
+        assert_eq!(isolate(&a).unwrap().0.samples, v);
+        assert_eq!(layout_name(&a), "3.1");
+    }
+
+    #[test]
+    fn test_stereo_centre_extraction_drops_panned_music() {
+        // Voice in the middle, a different instrument hard left and hard right
+        let n = SR as usize;
+        let v = voice(SR, n);
+        let (left, right) = (tone(SR, 1510.0, 0.3, n), tone(SR, 2330.0, 0.3, n));
+        let l: Vec<f32> = v.iter().zip(&left).map(|(a, b)| a + b).collect();
+        let r: Vec<f32> = v.iter().zip(&right).map(|(a, b)| a + b).collect();
+        let (d, info) = isolate(&audio(2, 0x3, &[&l, &r])).unwrap();
+        assert_eq!((info.layout.as_str(), info.method), ("stereo", "coherence"));
+        assert_eq!(d.samples.len(), n);
+        let mid: Vec<f32> = l.iter().zip(&r).map(|(a, b)| 0.5 * (a + b)).collect();
+        let (before, after) = (error_db(&mid, &v), error_db(&d.samples, &v));
+        assert!(after < before - 10.0, "mid {:.1} dB, extracted {:.1} dB", before, after);
+        // A centred source alone comes through unchanged
+        let (d, _) = isolate(&audio(2, 0x3, &[&v, &v])).unwrap();
+        assert!(error_db(&d.samples, &v) < -40.0);
+    }
+
+    #[test]
+    fn test_mono_passes_through_and_parse() {
+        let v = voice(SR, 1000);
+        let (d, info) = isolate(&audio(1, 0, &[&v])).unwrap();
+        assert_eq!((info.layout.as_str(), info.method, d.samples), ("mono", "mono", v));
+        let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(DialogueMode::parse(&args(&[])).unwrap(), DialogueMode::Off);
+        assert_eq!(DialogueMode::parse(&args(&["--dialogue", "clip"])).unwrap(), DialogueMode::Clip);
+        assert!(DialogueMode::parse(&args(&["--dialogue=centre"])).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+// Speech enhancement between decode and F0: music and effects under dialogue cause octave
+// jumps and false voicing, so the analysis input can be cleaned first. The enhanced audio is
+// also written next to the clip (`<stem>_enh.wav`) for playback.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use anyhow::{Result, Context};
+use serde::Serialize;
+
+use crate::stft::Stft;
+use crate::{cli, wav};
+
+pub trait Enhancer: Send + Sync + std::fmt::Debug {
+    // Short name for logs, results and the clip comment
+    fn name(&self) -> &'static str;
+    // Enhanced copy of mono `input` at `sample_rate`, same length
+    fn enhance(&self, input: &[f32], sample_rate: u32) -> Result<Vec<f32>>;
+}
+
+// What was done to a clip, reported with the cut
+#[derive(Debug, Clone, Serialize)]
+pub struct Enhancement {
+    pub method: &'static str,
+    pub path: String,
+    pub elapsed_ms: u64,
+}
+
+// `--enhance wiener` or `--enhance onnx --enhance-model <file.onnx> [--enhance-rate <Hz>]`;
+// Ok(None) when not given
+pub fn parse_enhancer(args: &[String]) -> Result<Option<Arc<dyn Enhancer>>> {
+    match cli::cli_value(args, "--enhance").as_deref() {
+        None => Ok(None),
+        Some("wiener") => Ok(Some(Arc::new(Wiener))),
+        Some("onnx") => {
+            let model = cli::cli_value(args, "--enhance-model").context("--enhance onnx needs --enhance-model <file.onnx>")?;
+            let rate = match cli::parse_f64(args, "--enhance-rate")? {
+                Some(r) if (8000.0..=192000.0).contains(&r) => r as u32,
+                Some(r) => anyhow::bail!("--enhance-rate must be between 8000 and 192000 Hz, got {}", r),
+                None => ONNX_DEFAULT_RATE,
+            };
+            load_onnx(Path::new(&model), rate)
+        }
+        Some(v) => anyhow::bail!("unknown --enhance '{}' (wiener|onnx)", v),
+    }
+}
+
+#[cfg(feature = "onnx")]
+fn load_onnx(model: &Path, rate: u32) -> Result<Option<Arc<dyn Enhancer>>> {
+    Ok(Some(Arc::new(onnx::OnnxEnhancer::load(model, rate)?)))
+}
+
+#[cfg(not(feature = "onnx"))]
+fn load_onnx(_model: &Path, _rate: u32) -> Result<Option<Arc<dyn Enhancer>>> {
+    anyhow::bail!("--enhance onnx: built without the `onnx` feature")
+}
+
+// Run `enhancer` and time it
+pub fn run(enhancer: &dyn Enhancer, mono: &[f32], sample_rate: u32) -> Result<(Vec<f32>, u64)> {
+    let started = Instant::now();
+    let out = enhancer.enhance(mono, sample_rate)
+        .with_context(|| format!("{} enhancement", enhancer.name()))?;
+    Ok((out, started.elapsed().as_millis() as u64))
+}
+
+// Write the enhanced mono audio next to the clip, noting the method in the comment
+pub fn write_enhanced(
+    path: &Path,
+    mono: &[f32],
+    sample_rate: u32,
+    method: &'static str,
+    elapsed_ms: u64,
+    meta: &wav::WavMetadata,
+) -> Result<Enhancement> {
+    let mut meta = meta.clone();
+    meta.append_comment(&format!("enhanced ({})", method));
+    let spec = wav::WavSpec { sample_rate, channels: 1, format: wav::SampleFormat::Pcm16 };
+    wav::write(path, mono, spec, &meta)?;
+    Ok(Enhancement { method, path: path.to_string_lossy().to_string(), elapsed_ms })
+}
+
+// `<stem>_enh.wav` beside a clip
+pub fn enhanced_path(clip: &Path) -> PathBuf {
+    let stem = clip.file_stem().and_then(|s| s.to_str()).unwrap_or("clip");
+    clip.with_file_name(format!("{}_enh.wav", stem))
+}
+
+// STFT frame
+const FRAME_S: f64 = 0.032;
+// Noise spectrum: mean of the quietest share of frames (pauses and padding: music only)
+const NOISE_SHARE: f64 = 0.2;
+// Decision-directed a priori SNR smoothing (Ephraim–Malah)
+const DD_ALPHA: f32 = 0.98;
+// Lowest gain per bin (-20 dB): deeper cuts leave musical noise
+const GAIN_FLOOR: f32 = 0.1;
+
+// Built-in Wiener filter with a noise spectrum taken from the clip's quietest frames
+#[derive(Debug, Clone, Copy)]
+pub struct Wiener;
+
+impl Enhancer for Wiener {
+    fn name(&self) -> &'static str { "wiener" }
+
+    fn enhance(&self, input: &[f32], sample_rate: u32) -> Result<Vec<f32>> {
+        let stft = Stft::new(FRAME_S, sample_rate);
+        let mut spectra = stft.analyze(input)?;
+        if spectra.is_empty() { return Ok(Vec::new()); }
+
+        // Noise power per bin from the quietest frames
+        let bins = stft.bins();
+        let frames = spectra.len();
+        let mut by_energy: Vec<(f32, usize)> = spectra.iter().enumerate()
+            .map(|(i, s)| (s.iter().map(|c| c.norm_sqr()).sum::<f32>(), i))
+            .collect();
+        by_energy.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let quiet = ((frames as f64 * NOISE_SHARE).ceil() as usize).clamp(1, frames);
+        let mut noise = vec![0.0f32; bins];
+        for &(_, i) in &by_energy[..quiet] {
+            for (nk, c) in noise.iter_mut().zip(&spectra[i]) { *nk += c.norm_sqr() / quiet as f32; }
+        }
+
+        let mut prev_clean = vec![0.0f32; bins];
+        for spec in spectra.iter_mut() {
+            for (k, c) in spec.iter_mut().enumerate() {
+                let power = c.norm_sqr();
+                let nk = noise[k].max(1e-12);
+                let prio = DD_ALPHA * prev_clean[k] / nk + (1.0 - DD_ALPHA) * (power / nk - 1.0).max(0.0);
+                let g = (prio / (1.0 + prio)).max(GAIN_FLOOR);
+                *c *= g;
+                prev_clean[k] = g * g * power;
+            }
+        }
+        stft.synthesize(spectra, input.len())
+    }
+}
+
+// Enhancer that always fails, for the fallback paths of cut/batch and live mode
+#[cfg(test)]
+#[derive(Debug)]
+pub struct FailingEnhancer;
+
+#[cfg(test)]
+impl Enhancer for FailingEnhancer {
+    fn name(&self) -> &'static str { "failing" }
+    fn enhance(&self, _input: &[f32], _sample_rate: u32) -> Result<Vec<f32>> {
+        anyhow::bail!("model error")
+    }
+}
+
+// Sample rate ONNX models run at unless --enhance-rate says otherwise (MossFormerGAN-SE 16K)
+const ONNX_DEFAULT_RATE: u32 = 16000;
+
+#[cfg(feature = "onnx")]
+mod onnx {
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+    use anyhow::{Result, Context};
+    use ort::session::Session;
+    use ort::value::Tensor;
+
+    use crate::resample;
+
+    // Waveform-in, waveform-out model: input [1, samples] f32 at `sample_rate`, first output
+    // the enhanced waveform. Runs on the CPU execution provider.
+    pub struct OnnxEnhancer {
+        model: PathBuf,
+        sample_rate: u32,
+        // Session::run needs exclusive access; cuts run one model call at a time
+        session: Mutex<Session>,
+    }
+
+    impl std::fmt::Debug for OnnxEnhancer {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("OnnxEnhancer").field("model", &self.model).field("sample_rate", &self.sample_rate).finish()
+        }
+    }
+
+    impl OnnxEnhancer {
+        pub fn load(model: &Path, sample_rate: u32) -> Result<Self> {
+            if !model.is_file() {
+                anyhow::bail!("ONNX model not found: {}", model.display());
+            }
+            let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+            // ort panics when the onnxruntime library cannot be loaded
+            let built = std::panic::catch_unwind(|| {
+                Session::builder()
+                    .and_then(|b| b.with_intra_threads(threads))
+                    .and_then(|b| b.commit_from_file(model))
+            });
+            let session = built
+                .map_err(|_| anyhow::anyhow!("onnxruntime not found (set ORT_DYLIB_PATH)"))?
+                .map_err(|e| anyhow::anyhow!("{}", e))
+                .with_context(|| format!("load ONNX model {}", model.display()))?;
+            Ok(Self { model: model.to_path_buf(), sample_rate, session: Mutex::new(session) })
+        }
+    }
+
+    impl super::Enhancer for OnnxEnhancer {
+        fn name(&self) -> &'static str { "onnx" }
+
+        fn enhance(&self, input: &[f32], sample_rate: u32) -> Result<Vec<f32>> {
+            let x = resample::resample(input, sample_rate, self.sample_rate);
+            let tensor = Tensor::from_array(([1usize, x.len()], x)).map_err(|e| anyhow::anyhow!("{}", e))?;
+            let mut session = self.session.lock().map_err(|_| anyhow::anyhow!("onnx session poisoned"))?;
+            let outputs = session.run(ort::inputs![tensor]).map_err(|e| anyhow::anyhow!("onnx run: {}", e))?;
+            let (_, y) = outputs[0].try_extract_tensor::<f32>().map_err(|e| anyhow::anyhow!("onnx output: {}", e))?;
+            let mut out = resample::resample(y, self.sample_rate, sample_rate);
+            out.resize(input.len(), 0.0);
+            Ok(out)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::noise;
+
+    const SR: u32 = 24000;
+
+    fn args(v: &[&str]) -> Vec<String> { v.iter().map(|s| s.to_string()).collect() }
+
+    // 160 Hz voice-like bursts (harmonics) in 0.4 s of every 0.6 s, after 0.3 s of lead-in
+    fn bursts(n: usize) -> Vec<f32> {
+        (0..n).map(|i| {
+            let t = i as f32 / SR as f32;
+            let on = t > 0.3 && (t - 0.3) % 0.6 < 0.4;
+            if !on { return 0.0; }
+            (1..6).map(|h| (std::f32::consts::TAU * 160.0 * h as f32 * t).sin() * 0.2 / h as f32).sum()
+        }).collect()
+    }
+
+    fn err_db(x: &[f32], clean: &[f32]) -> f32 {
+        let e: f32 = x.iter().zip(clean).map(|(a, b)| (a - b).powi(2)).sum();
+        let s: f32 = clean.iter().map(|v| v * v).sum();
+        10.0 * (e / s).log10()
+    }
+
+    #[test]
+    fn test_wiener_removes_noise_and_keeps_clean_speech() {
+        let clean = bursts(SR as usize * 3);
+        let noisy: Vec<f32> = clean.iter().zip(noise(987654321, clean.len(), 0.05)).map(|(c, n)| c + n).collect();
+        let out = Wiener.enhance(&noisy, SR).unwrap();
+        assert_eq!(out.len(), noisy.len());
+        let (before, after) = (err_db(&noisy, &clean), err_db(&out, &clean));
+        assert!(after < before - 6.0, "error {:.1} dB -> {:.1} dB", before, after);
+        // Nothing to remove: the signal comes back as it went in
+        let out = Wiener.enhance(&clean, SR).unwrap();
+        assert!(err_db(&out, &clean) < -30.0, "{:.1} dB", err_db(&out, &clean));
+        assert!(Wiener.enhance(&[], SR).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_enhancer() {
+        assert!(parse_enhancer(&args(&[])).unwrap().is_none());
+        assert_eq!(parse_enhancer(&args(&["--enhance", "wiener"])).unwrap().unwrap().name(), "wiener");
+        assert!(parse_enhancer(&args(&["--enhance", "rnnoise"])).is_err());
+        assert!(parse_enhancer(&args(&["--enhance", "onnx"])).is_err());
+        assert!(parse_enhancer(&args(&["--enhance", "onnx", "--enhance-model", "/nonexistent.onnx"])).is_err());
+        assert_eq!(enhanced_path(Path::new("out/ep01_1_2.wav")), Path::new("out").join("ep01_1_2_enh.wav"));
+    }
+}
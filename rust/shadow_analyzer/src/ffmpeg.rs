@@ -0,0 +1,68 @@
+// External ffmpeg helpers (fallback decoder backend): a WAV pipe for a window and an
+// ffprobe duration query.
+
+use std::process::{Child, ChildStdout, Command, Stdio};
+use anyhow::{Result, Context};
+
+// Get the audio stream and open input
+pub fn build_ffmpeg_base_args(media_path: &str, start_s: f64, end_s: f64, ff_index: Option<u64>) -> Vec<String> {
+    let mut args = Vec::new();
+    args.push("-hide_banner".to_string());
+    args.push("-loglevel".to_string());
+    args.push("error".to_string());
+    args.push("-nostdin".to_string());
+    args.push("-ss".to_string());
+    args.push(format!("{:.3}", start_s));
+    args.push("-to".to_string());
+    args.push(format!("{:.3}", end_s));
+    args.push("-i".to_string());
+    args.push(media_path.to_string());
+    if let Some(idx) = ff_index {
+        args.push("-map".to_string());
+        args.push(format!("0:{}", idx));
+    }
+    args
+}
+
+// Float WAV at 48 kHz in the source channel layout: the header carries the channel count
+// and (for surround) the speaker mask, so the centre channel can still be found
+pub fn spawn_wav_pipe(base_args: &[String]) -> Result<(Child, ChildStdout)> {
+    let mut args = base_args.to_vec();
+    args.push("-vn".to_string());
+    args.push("-sn".to_string());
+    args.push("-f".to_string());
+    args.push("wav".to_string());
+    args.push("-c:a".to_string());
+    args.push("pcm_f32le".to_string());
+    args.push("-ar".to_string());
+    args.push("48000".to_string());
+    args.push("pipe:1".to_string());
+
+    let mut cmd = Command::new("ffmpeg");
+    let mut child = cmd
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| "failed to spawn ffmpeg for wav pipe")?;
+
+    let stdout = child.stdout.take().context("failed to take stdout")?;
+    Ok((child, stdout))
+}
+
+// Container duration in seconds from `ffprobe -show_entries format=duration`
+pub fn probe_duration(media_path: &str) -> Result<f64> {
+    let out = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "format=duration", "-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(media_path)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .with_context(|| "failed to spawn ffprobe")?;
+    if !out.status.success() {
+        anyhow::bail!("ffprobe exited with status {:?}", out.status.code());
+    }
+    let text = String::from_utf8_lossy(&out.stdout);
+    text.trim().parse::<f64>().with_context(|| format!("ffprobe duration: '{}'", text.trim()))
+}
@@ -0,0 +1,248 @@
+// mpv JSON IPC client multiplexed by request_id.
+// A reader thread owns the read half: replies are routed to the caller waiting
+// on that request_id, everything else (property-change, client-message, ...)
+// is queued on an event channel so nothing is lost while a query is in flight.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use serde_json::Value;
+
+use crate::transport;
+
+// How long a caller waits for the reply to one command
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MpvEvent {
+    // observe_property notification; `data` is Null when the property is unavailable
+    PropertyChange { id: u64, name: String, data: Value },
+    // script-message / script-message-to sent by a Lua script or input.conf
+    ClientMessage { args: Vec<String> },
+    // Any other event (seek, playback-restart, end-file, ...)
+    Other { event: String, raw: Value },
+}
+
+impl MpvEvent {
+    fn from_json(v: Value) -> Option<MpvEvent> {
+        let event = v.get("event")?.as_str()?.to_string();
+        match event.as_str() {
+            "property-change" => Some(MpvEvent::PropertyChange {
+                id: v.get("id").and_then(|x| x.as_u64()).unwrap_or(0),
+                name: v.get("name").and_then(|x| x.as_str()).unwrap_or("").to_string(),
+                data: v.get("data").cloned().unwrap_or(Value::Null),
+            }),
+            "client-message" => Some(MpvEvent::ClientMessage {
+                args: v.get("args")
+                    .and_then(|a| a.as_array())
+                    .map(|a| a.iter().map(|x| x.as_str().unwrap_or("").to_string()).collect())
+                    .unwrap_or_default(),
+            }),
+            _ => Some(MpvEvent::Other { event, raw: v }),
+        }
+    }
+}
+
+type Pending = Arc<Mutex<HashMap<u64, mpsc::Sender<Value>>>>;
+
+pub struct MpvClient {
+    writer: Mutex<Box<dyn Write + Send>>,
+    next_id: AtomicU64,
+    pending: Pending,
+}
+
+// Send one JSON command (newline-delimited) to mpv IPC
+pub fn send_cmd<W: Write + ?Sized>(writer: &mut W, v: Value) -> io::Result<()> {
+    let s = v.to_string();
+    writer.write_all(s.as_bytes())?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+impl MpvClient {
+    // Connect once to the endpoint; the event receiver closes when mpv goes away
+    pub fn connect(path: &str) -> io::Result<(MpvClient, mpsc::Receiver<MpvEvent>)> {
+        let stream = transport::connect(path)?;
+        let reader = stream.try_clone()?;
+        Ok(MpvClient::new(reader, stream))
+    }
+
+    pub fn new<R, W>(reader: R, writer: W) -> (MpvClient, mpsc::Receiver<MpvEvent>)
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+        let (ev_tx, ev_rx) = mpsc::channel();
+        let pending_rd = Arc::clone(&pending);
+        thread::spawn(move || read_loop(BufReader::new(reader), pending_rd, ev_tx));
+        let client = MpvClient {
+            writer: Mutex::new(Box::new(writer)),
+            // Start above the small integers used in mpv docs/examples
+            next_id: AtomicU64::new(1000),
+            pending,
+        };
+        (client, ev_rx)
+    }
+
+    fn alloc_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    // Run a raw command and wait for its reply; returns the reply's `data` (Null if none)
+    pub fn command(&self, args: Vec<Value>) -> io::Result<Value> {
+        let id = self.alloc_id();
+        let (tx, rx) = mpsc::channel();
+        if let Ok(mut p) = self.pending.lock() { p.insert(id, tx); }
+        let sent = self.write_json(serde_json::json!({ "request_id": id, "command": args }));
+        if let Err(e) = sent {
+            if let Ok(mut p) = self.pending.lock() { p.remove(&id); }
+            return Err(e);
+        }
+        let reply = match rx.recv_timeout(REPLY_TIMEOUT) {
+            Ok(v) => v,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                if let Ok(mut p) = self.pending.lock() { p.remove(&id); }
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "mpv reply timeout"));
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "mpv closed pipe"));
+            }
+        };
+        match reply.get("error").and_then(|x| x.as_str()) {
+            Some("success") | None => Ok(reply.get("data").cloned().unwrap_or(Value::Null)),
+            Some(err) => Err(io::Error::other(format!("mpv: {}", err))),
+        }
+    }
+
+    pub fn get_property(&self, name: &str) -> io::Result<Value> {
+        self.command(vec![Value::from("get_property"), Value::from(name)])
+    }
+
+    // Numeric property, None when unavailable (e.g. sub-start with no subtitle)
+    pub fn get_property_f64(&self, name: &str) -> Option<f64> {
+        self.get_property(name).ok().and_then(|v| v.as_f64())
+    }
+
+    pub fn get_property_string(&self, name: &str) -> Option<String> {
+        self.get_property(name).ok().and_then(|v| v.as_str().map(|s| s.to_string()))
+    }
+
+    pub fn set_property<V: Into<Value>>(&self, name: &str, value: V) -> io::Result<()> {
+        self.command(vec![Value::from("set_property"), Value::from(name), value.into()]).map(|_| ())
+    }
+
+    pub fn observe_property(&self, id: u64, name: &str) -> io::Result<()> {
+        self.command(vec![Value::from("observe_property"), Value::from(id), Value::from(name)]).map(|_| ())
+    }
+
+    pub fn unobserve_property(&self, id: u64) -> io::Result<()> {
+        self.command(vec![Value::from("unobserve_property"), Value::from(id)]).map(|_| ())
+    }
+
+    pub fn request_event(&self, name: &str, enable: bool) -> io::Result<()> {
+        self.command(vec![Value::from("request_event"), Value::from(name), Value::from(enable)]).map(|_| ())
+    }
+
+    fn write_json(&self, v: Value) -> io::Result<()> {
+        let mut w = self.writer.lock().map_err(|_| io::Error::other("ipc writer poisoned"))?;
+        send_cmd(&mut **w, v)
+    }
+}
+
+// Route each incoming line: replies to their waiter, events to the queue
+fn read_loop<R: BufRead>(mut reader: R, pending: Pending, events: mpsc::Sender<MpvEvent>) {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let Ok(v) = serde_json::from_str::<Value>(&line) else { continue };
+        if let Some(id) = v.get("request_id").and_then(|x| x.as_u64()) {
+            if v.get("event").is_none() {
+                let waiter = pending.lock().ok().and_then(|mut p| p.remove(&id));
+                if let Some(tx) = waiter { let _ = tx.send(v); }
+                continue;
+            }
+        }
+        if let Some(ev) = MpvEvent::from_json(v) {
+            // Receiver dropped: nobody is listening anymore
+            if events.send(ev).is_err() { break; }
+        }
+    }
+    // Wake every waiter with a disconnect and close the event channel
+    if let Ok(mut p) = pending.lock() { p.clear(); }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+
+    // Fake mpv that interleaves an event before every reply
+    fn spawn_fake(server: UnixStream) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            let mut r = BufReader::new(server.try_clone().unwrap());
+            let mut w = server;
+            let mut line = String::new();
+            while r.read_line(&mut line).unwrap_or(0) > 0 {
+                let v: Value = serde_json::from_str(&line).unwrap();
+                line.clear();
+                let id = v["request_id"].as_u64().unwrap();
+                let name = v["command"][1].as_str().unwrap_or("").to_string();
+                send_cmd(&mut w, serde_json::json!({
+                    "event": "property-change", "id": 201, "name": "sub-text", "data": name
+                })).unwrap();
+                send_cmd(&mut w, serde_json::json!({
+                    "event": "client-message", "args": ["cut_current_sub"]
+                })).unwrap();
+                let reply = if name == "missing" {
+                    serde_json::json!({ "request_id": id, "error": "property unavailable" })
+                } else {
+                    serde_json::json!({ "request_id": id, "error": "success", "data": 1.5 })
+                };
+                send_cmd(&mut w, reply).unwrap();
+            }
+        })
+    }
+
+    #[test]
+    fn test_replies_routed_and_events_kept() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let fake = spawn_fake(b);
+        let shut = a.try_clone().unwrap();
+        let (client, events) = MpvClient::new(a.try_clone().unwrap(), a);
+        assert_eq!(client.get_property_f64("sub-start"), Some(1.5));
+        assert!(client.get_property("missing").is_err());
+        let got: Vec<MpvEvent> = events.try_iter().collect();
+        assert_eq!(got.len(), 4);
+        assert_eq!(got[0], MpvEvent::PropertyChange {
+            id: 201, name: "sub-text".into(), data: Value::from("sub-start"),
+        });
+        assert_eq!(got[1], MpvEvent::ClientMessage { args: vec!["cut_current_sub".into()] });
+        shut.shutdown(std::net::Shutdown::Both).unwrap();
+        fake.join().unwrap();
+    }
+
+    #[test]
+    fn test_request_ids_are_unique() {
+        let (a, _b) = UnixStream::pair().unwrap();
+        let (client, _events) = MpvClient::new(a.try_clone().unwrap(), a);
+        let ids: Vec<u64> = (0..5).map(|_| client.alloc_id()).collect();
+        for w in ids.windows(2) { assert!(w[1] > w[0]); }
+    }
+
+    #[test]
+    fn test_disconnect_fails_pending_and_closes_events() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let (client, events) = MpvClient::new(a.try_clone().unwrap(), a);
+        drop(b);
+        assert!(client.get_property("duration").is_err());
+        assert!(events.recv().is_err());
+    }
+}
@@ -0,0 +1,976 @@
+// Live mode: follow mpv over JSON IPC, cut the current subtitle line on request,
+// record the microphone alongside, and push results to the UI.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{analysis, cut, decode, dialogue, enhance, ipc, loudness, mic, pitch, subs, sync, vad, wav};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UiPayload {
+    pub text: Option<String>,
+    // Secondary subtitle (translation) shown with the line, if any
+    pub translation: Option<String>,
+    pub s: f64,
+    pub e: f64,
+    pub dur: f64,
+    pub ff_index: Option<u64>,
+    pub out_path: String,
+    pub latest_path: String,
+    // Optional microphone outputs
+    pub latest_mic_path: Option<String>,
+    pub mic_out_path: Option<String>,
+    // Time to decode the window (ms)
+    pub latency_ms: u64,
+    // Timing corrections folded into the window (s/e are audio stream times)
+    pub sub_delay: f64,
+    pub audio_delay: f64,
+    pub speed: f64,
+    // Whole-clip RMS and sample peak (linear)
+    pub rms: f32,
+    pub peak: f32,
+    // EBU R128 loudness, true peak, clipping and short-term contour of each clip
+    pub loudness_src: Option<loudness::Loudness>,
+    pub loudness_mic: Option<loudness::Loudness>,
+    // Original and speech-adjusted windows, with --auto-trim (s/e are then the adjusted one)
+    pub trim: Option<vad::Trim>,
+    // Subtitle offset suggested by the speech onsets of the recent cuts of this file
+    pub sub_offset: Option<sync::SubOffset>,
+    // Normalised playback copies (path and applied gain), with --normalize
+    pub norm_src: Option<loudness::Normalization>,
+    pub norm_mic: Option<loudness::Normalization>,
+    // Enhanced source (latest_enh.wav) that the F0 was taken from, with --enhance
+    pub enhanced: Option<enhance::Enhancement>,
+    // Source layout and how the dialogue was isolated, with --dialogue
+    pub dialogue: Option<dialogue::Dialogue>,
+    // Optional pitch outputs (Hz and ratios)
+    pub f0_src_median: Option<f32>,
+    pub f0_mic_median: Option<f32>,
+    pub voiced_src: Option<f32>,
+    pub voiced_mic: Option<f32>,
+    // Mean voicing confidence of the source line (0–1)
+    pub voicing_src: Option<f32>,
+    // Optional tiny F0 series (Hz), small, already downsampled
+    pub f0_src_series: Option<Vec<f32>>,
+    pub f0_mic_series: Option<Vec<f32>>,
+    // Set when decoding/analysis failed, so the UI can say why nothing shows up
+    pub error: Option<String>,
+    // Optional stages that failed; the cut went on without them
+    pub warnings: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ConnectionStatus { pub connected: bool, pub endpoint: String }
+
+// Latest payload/status slots plus a wake-up callback for whoever displays them
+// (the webview event loop, or nothing when running headless)
+#[derive(Clone)]
+pub struct UiBridge {
+    pub payload: Arc<Mutex<Option<UiPayload>>>,
+    pub status: Arc<Mutex<Option<ConnectionStatus>>>,
+    notify: Arc<dyn Fn() + Send + Sync>,
+    // Connected session, for actions started from the UI
+    session: Arc<Mutex<Option<LiveSession>>>,
+    // Last cut payload and mic take, kept after the display took the payload
+    latest: Arc<Mutex<LatestCut>>,
+}
+
+#[derive(Default)]
+struct LatestCut {
+    payload: Option<UiPayload>,
+    mic: Option<MicTake>,
+}
+
+// A finished microphone take and its levels
+#[derive(Clone, Debug)]
+struct MicTake {
+    // out_path of the cut it was recorded for
+    out_path: String,
+    latest_mic_path: String,
+    mic_out_path: String,
+    loudness: Option<loudness::Loudness>,
+    norm: Option<loudness::Normalization>,
+    f0: Option<analysis::F0Summary>,
+}
+
+impl MicTake {
+    fn apply(&self, p: &mut UiPayload) {
+        p.latest_mic_path = Some(self.latest_mic_path.clone());
+        p.mic_out_path = Some(self.mic_out_path.clone());
+        p.loudness_mic = self.loudness.clone();
+        p.norm_mic = self.norm.clone();
+        if let Some(f0) = &self.f0 {
+            p.f0_mic_median = f0.median_hz;
+            p.voiced_mic = Some(f0.voiced_ratio);
+            p.f0_mic_series = Some(f0.series.clone());
+        }
+    }
+}
+
+impl UiBridge {
+    pub fn new<F: Fn() + Send + Sync + 'static>(notify: F) -> Self {
+        Self {
+            payload: Arc::new(Mutex::new(None)),
+            status: Arc::new(Mutex::new(None)),
+            notify: Arc::new(notify),
+            session: Arc::new(Mutex::new(None)),
+            latest: Arc::default(),
+        }
+    }
+
+    pub fn notify(&self) {
+        (self.notify)();
+    }
+
+    // Publish a cut's payload, with its mic take if that finished first
+    pub fn send_payload(&self, mut p: UiPayload) {
+        if let Ok(mut latest) = self.latest.lock() {
+            if let Some(take) = latest.mic.as_ref().filter(|m| m.out_path == p.out_path) {
+                take.apply(&mut p);
+            }
+            latest.payload = Some(p.clone());
+            // Published under the lock, so a mic take merged meanwhile is not overwritten
+            if let Ok(mut guard) = self.payload.lock() { *guard = Some(p); }
+        }
+        self.notify();
+    }
+
+    // Merge a mic take into the latest payload of its cut; the rest of that payload
+    // (levels, offset, trim, F0) stays as the pipeline left it
+    fn send_mic(&self, take: MicTake) {
+        let Ok(mut latest) = self.latest.lock() else { return };
+        let merged = latest.payload.as_mut().filter(|p| p.out_path == take.out_path).map(|p| {
+            take.apply(p);
+            p.clone()
+        });
+        latest.mic = Some(take);
+        if let Some(p) = merged {
+            if let Ok(mut guard) = self.payload.lock() { *guard = Some(p); }
+            drop(latest);
+            self.notify();
+        }
+    }
+
+    pub fn send_status(&self, connected: bool, endpoint: &str) {
+        if let Ok(mut g) = self.status.lock() {
+            *g = Some(ConnectionStatus { connected, endpoint: endpoint.to_string() });
+        }
+        self.notify();
+    }
+
+    // Set mpv's sub-delay to the current estimate; nothing happens while disconnected
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))] // the webview's button; headless has none
+    pub fn apply_sub_offset(&self) {
+        let Some(session) = self.session.lock().ok().and_then(|g| g.clone()) else { return };
+        // Off the UI thread: mpv replies take a moment
+        thread::spawn(move || apply_sub_offset(&session));
+    }
+}
+
+// One mpv connection and the sub offset estimate of its cuts
+#[derive(Clone)]
+struct LiveSession {
+    client: Arc<ipc::MpvClient>,
+    offsets: Arc<Mutex<sync::OffsetEstimator>>,
+}
+
+// observe_property ids (distinct from request ids, which the client allocates)
+const OBSERVE_TIME_POS: u64 = 101;
+const OBSERVE_SUB_TEXT: u64 = 201;
+const OBSERVE_SECONDARY_SUB_TEXT: u64 = 202;
+const OBSERVE_SUB_DELAY: u64 = 301;
+const OBSERVE_AUDIO_DELAY: u64 = 302;
+const OBSERVE_SPEED: u64 = 303;
+
+// Clips kept per type in the output directory (latest*.wav excluded)
+const KEEP_CLIPS: usize = 5;
+
+// Knobs for the live loop; `Default` is what the app uses, tests override them
+#[derive(Clone, Debug)]
+pub struct LiveOptions {
+    pub out_dir: PathBuf,
+    // Record the microphone alongside each cut
+    pub mic: bool,
+    // Go back to connect-retry after mpv disconnects instead of returning
+    pub reconnect: bool,
+    pub decoder: decode::Decoder,
+    // Also write loudness-normalised copies of latest.wav / latest_mic.wav for playback
+    pub normalize_lufs: Option<f32>,
+    // Move each cut to the speech found near the line
+    pub auto_trim: bool,
+    // Clean the source before F0 and write latest_enh.wav for playback
+    pub enhancer: Option<Arc<dyn enhance::Enhancer>>,
+    // Analyse (and with Clip, save) the dialogue isolated from the mix
+    pub dialogue: dialogue::DialogueMode,
+    // Pitch estimator for the F0 pass
+    pub pitch: pitch::PitchMethod,
+    // Set to stop run_analyzer: it returns from connect-retry, or once mpv disconnects
+    pub stop: Arc<AtomicBool>,
+}
+
+impl Default for LiveOptions {
+    fn default() -> Self {
+        Self {
+            out_dir: cut::default_out_dir(),
+            mic: true,
+            reconnect: true,
+            decoder: decode::Decoder::Auto,
+            normalize_lufs: None,
+            auto_trim: false,
+            enhancer: None,
+            dialogue: dialogue::DialogueMode::Off,
+            pitch: pitch::PitchMethod::Mpm,
+            stop: Arc::default(),
+        }
+    }
+}
+
+// Connect, serve one mpv session, and go back to connect-retry whenever mpv closes the pipe
+pub fn run_analyzer(ipc_path: String, ui: UiBridge, mic_selected: Arc<Mutex<Option<String>>>, opts: LiveOptions) {
+    loop {
+        ui.send_status(false, &ipc_path);
+        // Connect to the mpv JSON IPC endpoint (named pipe or Unix socket; retry until mpv is up)
+        let (client, events) = loop {
+            if opts.stop.load(Ordering::Relaxed) { return; }
+            match ipc::MpvClient::connect(&ipc_path) {
+                Ok(c) => break c,
+                Err(_) => {
+                    sleep(Duration::from_millis(300));
+                }
+            }
+        };
+        eprintln!("connected to mpv IPC at {}", ipc_path);
+        let session = LiveSession { client: Arc::new(client), offsets: Arc::default() };
+        if let Ok(mut g) = ui.session.lock() { *g = Some(session.clone()); }
+        ui.send_status(true, &ipc_path);
+        run_session(&session, events, &ui, &mic_selected, &opts);
+        if let Ok(mut g) = ui.session.lock() { *g = None; }
+        if !opts.reconnect || opts.stop.load(Ordering::Relaxed) {
+            eprintln!("mpv IPC disconnected");
+            ui.send_status(false, &ipc_path);
+            return;
+        }
+        eprintln!("mpv IPC disconnected; waiting for mpv to come back");
+    }
+}
+
+// Pauses playback once time-pos reaches a threshold (observes time-pos only while armed).
+// With repeats left it seeks back to `loop_from` instead of pausing.
+#[derive(Default)]
+struct PauseWatch {
+    until: Option<f64>,
+    observing: bool,
+    loop_from: f64,
+    repeats_left: u32,
+    // After a loop seek, ignore stale positions until one before `until` shows up
+    rewinding: bool,
+}
+
+impl PauseWatch {
+    fn arm(&mut self, client: &ipc::MpvClient, from: f64, t: f64, repeats: u32) {
+        self.until = Some(t);
+        self.loop_from = from;
+        self.repeats_left = repeats;
+        self.rewinding = false;
+        if !self.observing {
+            let _ = client.observe_property(OBSERVE_TIME_POS, "time-pos");
+            self.observing = true;
+        }
+    }
+
+    fn on_time_pos(&mut self, client: &ipc::MpvClient, cur: f64) {
+        let Some(t) = self.until else { return };
+        if self.rewinding {
+            if cur < t { self.rewinding = false; }
+            return;
+        }
+        if cur >= t && self.repeats_left > 0 {
+            self.repeats_left -= 1;
+            self.rewinding = true;
+            let _ = client.set_property("time-pos", self.loop_from);
+        } else if cur >= t {
+            let _ = client.set_property("pause", true);
+            if self.observing {
+                let _ = client.unobserve_property(OBSERVE_TIME_POS);
+                self.observing = false;
+            }
+            self.until = None;
+        }
+    }
+}
+
+// Pause, seek to the padded line start and arm the watch at its end; the caller unpauses.
+// `plays` > 1 loops the window that many times in total.
+fn cue_line(client: &ipc::MpvClient, watch: &mut PauseWatch, win: &cut::CorrectedWindow, timing: &cut::TimingCorrection, plays: u32) {
+    let _ = client.set_property("pause", true);
+    let _ = client.set_property("time-pos", win.play_s);
+    // Set watcher threshold ~20 ms (wall clock) before the end
+    let until = (win.play_e - 0.02 * timing.speed.max(0.01)).max(0.0);
+    watch.arm(client, win.play_s, until, plays.saturating_sub(1));
+}
+
+// Play `line` (without cutting) `plays` times, pausing at its end
+fn play_line(client: &ipc::MpvClient, watch: &mut PauseWatch, line: &SubLine, timing: &cut::TimingCorrection, plays: u32) {
+    let dur = client.get_property_f64("duration").unwrap_or(0.0);
+    let win = cut::corrected_window(line.start, line.end, dur, cut::DEFAULT_PAD_S, timing);
+    if win.play_s >= win.play_e { return; }
+    cue_line(client, watch, &win, timing, plays);
+    let _ = client.set_property("pause", false);
+}
+
+// Move to the previous/next subtitle with mpv's sub-seek; the line it lands on arrives
+// as the next sub-text change. False when there is no line in that direction.
+fn seek_line(client: &ipc::MpvClient, line: Option<&SubLine>, timing: &cut::TimingCorrection, skip: i64) -> bool {
+    // Anchor inside the current line first, so the padding pre-roll before it
+    // does not make sub-seek count from the previous line
+    if let Some(l) = line {
+        let _ = client.set_property("time-pos", l.start + timing.sub_delay + 0.001);
+    }
+    match client.command(vec![Value::from("sub-seek"), Value::from(skip)]) {
+        Ok(_) => true,
+        Err(e) => {
+            eprintln!("sub-seek {} failed: {}", skip, e);
+            false
+        }
+    }
+}
+
+// A prev/next command waiting for the line sub-seek lands on
+struct PendingSeek {
+    cmd: &'static str,
+    // Start of the line it left, which the anchoring seek may show again first
+    from_start: Option<f64>,
+}
+
+// Subtitle line as last seen on screen (raw, without padding)
+#[derive(Clone, Debug)]
+struct SubLine {
+    text: Option<String>,
+    // Matching secondary-sub-text, attached when the line is cut
+    translation: Option<String>,
+    start: f64,
+    end: f64,
+}
+
+// Secondary (translation) line to attach to `primary`: it must mostly overlap the
+// primary window; without times (older mpv) the last one seen is taken as-is
+fn translation_for(primary: &SubLine, secondary: Option<&SubLine>) -> Option<String> {
+    let sec = secondary?;
+    let text = sec.text.clone().filter(|t| !t.trim().is_empty())?;
+    if sec.end <= sec.start || subs::mostly_overlaps(primary.start, primary.end, sec.start, sec.end) {
+        Some(text)
+    } else {
+        None
+    }
+}
+
+// One connection's lifetime; all per-playback state lives here so a reconnect starts clean
+fn run_session(
+    session: &LiveSession,
+    events: mpsc::Receiver<ipc::MpvEvent>,
+    ui: &UiBridge,
+    mic_selected: &Arc<Mutex<Option<String>>>,
+    opts: &LiveOptions,
+) {
+    let client = session.client.as_ref();
+    // Subscribe to client-message events so we see script-message triggers
+    let _ = client.request_event("client-message", true);
+    // Observe subtitle changes to keep current_line updated continuously
+    let _ = client.observe_property(OBSERVE_SUB_TEXT, "sub-text");
+    let _ = client.observe_property(OBSERVE_SECONDARY_SUB_TEXT, "secondary-sub-text");
+    // Timing state that moves the audio relative to sub-start/sub-end
+    let _ = client.observe_property(OBSERVE_SUB_DELAY, "sub-delay");
+    let _ = client.observe_property(OBSERVE_AUDIO_DELAY, "audio-delay");
+    let _ = client.observe_property(OBSERVE_SPEED, "speed");
+
+    let mut watch = PauseWatch::default();
+    let mut current_line: Option<SubLine> = None;
+    let mut secondary_line: Option<SubLine> = None;
+    let mut timing = cut::TimingCorrection::default();
+    let mut pending_seek: Option<PendingSeek> = None;
+    // Events queue up in the client while we wait on replies; none are dropped
+    for ev in events.iter() {
+        match ev {
+            // Handle property-change for time-pos to enforce pause at end
+            ipc::MpvEvent::PropertyChange { name, data, .. } if name == "time-pos" => {
+                if let Some(cur) = data.as_f64() {
+                    watch.on_time_pos(client, cur);
+                }
+            }
+            ipc::MpvEvent::PropertyChange { name, data, .. }
+                if name == "sub-delay" || name == "audio-delay" || name == "speed" =>
+            {
+                if let Some(v) = data.as_f64() {
+                    match name.as_str() {
+                        "sub-delay" => timing.sub_delay = v,
+                        "audio-delay" => timing.audio_delay = v,
+                        _ => timing.speed = v,
+                    }
+                }
+            }
+            ipc::MpvEvent::PropertyChange { name, data, .. } if name == "sub-text" => {
+                // Update current_line when a subtitle becomes visible
+                if let Some(text_val) = data.as_str().map(|s| s.to_string()) {
+                    // Query sub-start and sub-end to capture window
+                    let s_now = client.get_property_f64("sub-start").unwrap_or(0.0);
+                    let e_now = client.get_property_f64("sub-end").unwrap_or(0.0);
+                    if e_now > s_now {
+                        let line = SubLine { text: Some(text_val), translation: None, start: s_now, end: e_now };
+                        eprintln!("current_line updated: s={:.3} e={:.3}", s_now, e_now);
+                        if let Some(p) = pending_seek.take_if(|p| p.from_start != Some(s_now)) {
+                            eprintln!("{}: s={:.3} e={:.3}", p.cmd, s_now, e_now);
+                            play_line(client, &mut watch, &line, &timing, 1);
+                        }
+                        current_line = Some(line);
+                    }
+                }
+            }
+            ipc::MpvEvent::PropertyChange { name, data, .. } if name == "secondary-sub-text" => {
+                // Keep the last non-empty translation line, like current_line
+                if let Some(text_val) = data.as_str().filter(|t| !t.trim().is_empty()).map(|s| s.to_string()) {
+                    let s_now = client.get_property_f64("secondary-sub-start").unwrap_or(0.0);
+                    let e_now = client.get_property_f64("secondary-sub-end").unwrap_or(0.0);
+                    secondary_line = Some(SubLine { text: Some(text_val), translation: None, start: s_now, end: e_now });
+                }
+            }
+            ipc::MpvEvent::ClientMessage { args } => {
+                eprintln!("client-message: {:?}", args);
+                // Any new command supersedes a prev/next still waiting for its line
+                pending_seek = None;
+                match args.first().map(|x| x.as_str()) {
+                    Some("cut_current_sub") => {
+                        eprintln!("trigger: cut_current_sub");
+                        // Always use current_line (start/end from the last visible subtitle)
+                        match current_line.clone() {
+                            Some(mut line) => {
+                                line.translation = translation_for(&line, secondary_line.as_ref());
+                                cut_line(session, &line, &timing, &mut watch, ui, mic_selected, opts)
+                            }
+                            None => eprintln!("No current_line available; skipping cut"),
+                        }
+                    }
+                    Some("apply_sub_offset") => apply_sub_offset(session),
+                    Some("replay_line") => match current_line.as_ref() {
+                        Some(line) => play_line(client, &mut watch, line, &timing, 1),
+                        None => eprintln!("No current_line available; nothing to replay"),
+                    },
+                    Some("loop_line") => {
+                        let plays = args.get(1).and_then(|n| n.parse::<u32>().ok()).unwrap_or(3).clamp(1, 100);
+                        match current_line.as_ref() {
+                            Some(line) => play_line(client, &mut watch, line, &timing, plays),
+                            None => eprintln!("No current_line available; nothing to loop"),
+                        }
+                    }
+                    Some(cmd @ ("prev_line" | "next_line")) => {
+                        let (cmd, skip) = if cmd == "prev_line" { ("prev_line", -1) } else { ("next_line", 1) };
+                        if seek_line(client, current_line.as_ref(), &timing, skip) {
+                            pending_seek = Some(PendingSeek { cmd, from_start: current_line.as_ref().map(|l| l.start) });
+                        } else {
+                            eprintln!("{}: no subtitle line there", cmd);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Selected audio track's ff-index from mpv's track-list
+fn selected_audio_ff_index(track_list: &Value) -> Option<u64> {
+    let arr = track_list.as_array()?;
+    for t in arr {
+        let is_audio = t.get("type").and_then(|x| x.as_str()) == Some("audio");
+        let selected = t.get("selected").and_then(|x| x.as_bool()) == Some(true);
+        if is_audio && selected {
+            return t.get("ff-index").and_then(|x| x.as_u64());
+        }
+    }
+    None
+}
+
+fn cut_line(
+    session: &LiveSession,
+    line: &SubLine,
+    timing: &cut::TimingCorrection,
+    watch: &mut PauseWatch,
+    ui: &UiBridge,
+    mic_selected: &Arc<Mutex<Option<String>>>,
+    opts: &LiveOptions,
+) {
+    let client = session.client.as_ref();
+    // Query properties; events arriving meanwhile stay queued in the client
+    let dur = client.get_property_f64("duration").unwrap_or(0.0);
+    let media_path = client.get_property_string("path")
+        .unwrap_or_else(|| "<unknown>".to_string());
+    let ff_index = client.get_property("track-list").ok()
+        .and_then(|tl| selected_audio_ff_index(&tl));
+    eprintln!("ff-index: {:?}", ff_index);
+
+    // Padding + clamping, on the playback timeline and in the audio stream
+    let text = line.text.clone();
+    let win = cut::corrected_window(line.start, line.end, dur, cut::DEFAULT_PAD_S, timing);
+    let (s, e) = (win.audio_s, win.audio_e);
+    if *timing != cut::TimingCorrection::default() {
+        eprintln!("timing: sub-delay={:.3} audio-delay={:.3} speed={:.2} -> play {:.3}-{:.3}",
+            timing.sub_delay, timing.audio_delay, timing.speed, win.play_s, win.play_e);
+    }
+
+    // create output directory
+    let out_dir = opts.out_dir.clone();
+    let _ = std::fs::create_dir_all(&out_dir);
+    let out_path = cut::clip_path(&out_dir, &media_path, s, e);
+    let latest_path = out_dir.join("latest.wav");
+    eprintln!("out_path: {:?}", out_path);
+
+    // Decode, write and analyze the window in the background (non-blocking)
+    if media_path != "<unknown>" && s < e {
+        // Playback control: seek to the line and unpause; pause again at its end via watcher
+        cue_line(client, watch, &win, timing, 1);
+
+        // Prepare mic paths
+        let latest_mic_path = out_dir.join("latest_mic.wav");
+        let mic_out_path = out_path.with_file_name(format!(
+            "{}_mic.wav",
+            out_path.file_stem().and_then(|x| x.to_str()).unwrap_or("clip")
+        ));
+
+        let shift = timing.sub_delay - timing.audio_delay;
+        let src = WindowSource {
+            decoder: opts.decoder,
+            media_path: media_path.clone(),
+            s,
+            e,
+            line_s: line.start + shift,
+            line_e: line.end + shift,
+            ff_index,
+            auto_trim: opts.auto_trim,
+        };
+        // schedule retention cleanup (keep 5 unique clips)
+        cleanup_old_clips(&out_dir, KEEP_CLIPS, &[&out_path, &latest_path]);
+
+        let payload = UiPayload {
+            text: text.clone(),
+            translation: line.translation.clone(),
+            s,
+            e,
+            dur,
+            ff_index,
+            out_path: out_path.to_string_lossy().to_string(),
+            latest_path: latest_path.to_string_lossy().to_string(),
+            latest_mic_path: None,
+            mic_out_path: None,
+            latency_ms: 0,
+            sub_delay: timing.sub_delay,
+            audio_delay: timing.audio_delay,
+            speed: timing.speed,
+            rms: 0.0,
+            peak: 0.0,
+            loudness_src: None,
+            loudness_mic: None,
+            trim: None,
+            sub_offset: None,
+            norm_src: None,
+            norm_mic: None,
+            enhanced: None,
+            dialogue: None,
+            f0_src_median: None,
+            f0_mic_median: None,
+            voiced_src: None,
+            voiced_mic: None,
+            voicing_src: None,
+            f0_src_series: None,
+            f0_mic_series: None,
+            error: None,
+            warnings: Vec::new(),
+        };
+
+        // Start mic recorder: use selected device, else fallback to first detected
+        let mic_device_sel = mic_selected.lock().ok().and_then(|g| g.clone());
+        let mut chosen_dev: Option<String> = mic_device_sel.clone().filter(|_| opts.mic);
+        if chosen_dev.is_none() && opts.mic {
+            if let Some(first) = mic::fallback_mic_device() {
+                eprintln!("No mic selected; falling back to first device: '{}'", first.name);
+                chosen_dev = Some(first.id.clone());
+            }
+        }
+        if let Some(dev) = chosen_dev.as_deref() {
+            // Merge the take's paths and levels into the cut's payload once the recording is done
+            let cut_out = payload.out_path.clone();
+            let ui_mic = ui.clone();
+            let out_dir_mic = out_dir.clone();
+            let (latest_mic_done, mic_out_done) = (latest_mic_path.clone(), mic_out_path.clone());
+            let (normalize_lufs, method) = (opts.normalize_lufs, opts.pitch);
+            // The user speaks along in wall-clock time, so the take scales with speed
+            let mic_s = timing.wall_s(win.play_e - win.play_s).max(0.0);
+            mic::spawn_mic_recorder(&latest_mic_path, &mic_out_path, mic_s, dev, move || {
+                // Cleanup retention for mic wavs
+                cleanup_old_clips(&out_dir_mic, KEEP_CLIPS, &[&latest_mic_done, &mic_out_done]);
+                ui_mic.send_mic(mic_take(&cut_out, &latest_mic_done, &mic_out_done, normalize_lufs, method));
+            });
+
+            // Optional readiness: wait up to ~150ms for file to exist and have size > 44 bytes
+            let start_ready = Instant::now();
+            loop {
+                let meta = std::fs::metadata(&latest_mic_path);
+                if let Ok(m) = meta {
+                    if m.len() > 44 { break; }
+                }
+                if start_ready.elapsed() > Duration::from_millis(150) { break; }
+                sleep(Duration::from_millis(25));
+            }
+        } else {
+            eprintln!("No microphone available; skipping mic capture.");
+        }
+
+        // Unpause playback now
+        let _ = client.set_property("pause", false);
+
+        let mut payload = payload;
+        if mic_device_sel.is_some() && opts.mic {
+            payload.latest_mic_path = Some(latest_mic_path.to_string_lossy().to_string());
+            payload.mic_out_path = Some(mic_out_path.to_string_lossy().to_string());
+        }
+        spawn_cut_pipeline(src, payload, &out_path, &latest_path, opts, &session.offsets, ui);
+    } else if media_path == "<unknown>" {
+        eprintln!("no active subtitle or unknown media path");
+    }
+
+    // Show quick OSD confirmation
+    let msg = if text.is_some() && s < e {
+        format!("cut {:.3}–{:.3} (ff={:?})", s, e, ff_index)
+    } else {
+        "no active subtitle".to_string()
+    };
+    let _ = client.command(vec![Value::from("show-text"), Value::from(msg), Value::from(1200)]);
+}
+
+// Levels and F0 of a finished take, and its normalised copy with a target
+fn mic_take(out_path: &str, latest_mic: &Path, mic_out: &Path, normalize_lufs: Option<f32>, method: pitch::PitchMethod) -> MicTake {
+    let mut take = MicTake {
+        out_path: out_path.to_string(),
+        latest_mic_path: latest_mic.to_string_lossy().to_string(),
+        mic_out_path: mic_out.to_string_lossy().to_string(),
+        loudness: None,
+        norm: None,
+        f0: None,
+    };
+    // One read of the take feeds the levels, the normalised copy and the F0
+    let read = wav::WavReader::open(latest_mic).and_then(|mut reader| {
+        let mut mono = Vec::with_capacity(reader.frames() as usize);
+        let f0 = analysis::summarize_f0_reader(&mut reader, cut::DEFAULT_PAD_S as f32, method, |b| mono.extend_from_slice(b))?;
+        Ok((mono, reader.info.sample_rate, reader.metadata, f0))
+    });
+    match read {
+        Ok((mono, sr, meta, f0)) => {
+            let levels = loudness::measure(&mono, sr, &[1.0]);
+            if let Some(target) = normalize_lufs {
+                let norm_path = loudness::normalized_path(latest_mic);
+                let spec = wav::WavSpec { sample_rate: sr, channels: 1, format: wav::SampleFormat::Pcm16 };
+                // The copy keeps the recording's tags (ffmpeg's ISFT), like the source copy keeps the clip's
+                match loudness::write_normalized(&norm_path, &mono, spec, &levels, target, &meta) {
+                    Ok(n) => take.norm = n,
+                    Err(e) => eprintln!("mic normalise failed: {:#}", e),
+                }
+            }
+            take.loudness = Some(levels);
+            // The take is as long as the padded window
+            take.f0 = Some(f0);
+        }
+        Err(e) => eprintln!("mic take read failed: {:#}", e),
+    }
+    take
+}
+
+// Move sub-delay to the estimate from the recent cuts once they agree; the OSD says what
+// happened either way
+fn apply_sub_offset(session: &LiveSession) {
+    let client = session.client.as_ref();
+    let media = client.get_property_string("path").unwrap_or_default();
+    let current = client.get_property_f64("sub-delay").unwrap_or(0.0);
+    let estimate = session.offsets.lock().ok().and_then(|o| o.estimate(&media, current));
+    let msg = match estimate {
+        Some(o) if o.stable => {
+            let v = (o.sub_delay * 1000.0).round() / 1000.0;
+            match client.set_property("sub-delay", v) {
+                Ok(()) => format!("sub-delay {:+.3} s ({:+.3} from {} cuts)", v, v - current, o.cuts),
+                Err(e) => format!("sub-delay not set: {}", e),
+            }
+        }
+        Some(o) => format!("sub offset not settled ({} cuts, spread {:.3} s)", o.cuts, o.spread),
+        None => "no sub offset estimate yet".to_string(),
+    };
+    eprintln!("apply_sub_offset: {}", msg);
+    let _ = client.command(vec![Value::from("show-text"), Value::from(msg), Value::from(2000)]);
+}
+
+// Audio window of one cut
+#[derive(Clone, Debug)]
+struct WindowSource {
+    decoder: decode::Decoder,
+    media_path: String,
+    s: f64,
+    e: f64,
+    // Unpadded line in the audio stream, for the clip's "line" marker
+    line_s: f64,
+    line_e: f64,
+    ff_index: Option<u64>,
+    // Keep the speech near the line (vad.rs) rather than the padded subtitle window
+    auto_trim: bool,
+}
+
+// Decode the window once (with room to search for speech) and fan the buffer out to the clips,
+// levels and F0. Levels reach the UI once latest.wav is playable, F0 with the unique clip that
+// embeds it; failures as `error`.
+fn spawn_cut_pipeline(
+    src: WindowSource,
+    payload: UiPayload,
+    out_path: &Path,
+    latest_path: &Path,
+    opts: &LiveOptions,
+    offsets: &Arc<Mutex<sync::OffsetEstimator>>,
+    ui: &UiBridge,
+) {
+    let (out_path, latest_path) = (out_path.to_path_buf(), latest_path.to_path_buf());
+    let (normalize_lufs, enhancer, dialogue_mode, method) = (opts.normalize_lufs, opts.enhancer.clone(), opts.dialogue, opts.pitch);
+    let (ui, offsets) = (ui.clone(), Arc::clone(offsets));
+    thread::spawn(move || {
+        let mut payload = payload;
+        let start_instant = Instant::now();
+        let (decode_s, decode_e) = vad::search_window(src.s, src.e, payload.dur);
+        let audio = match decode::decode_window(src.decoder, &src.media_path, decode_s, decode_e, src.ff_index) {
+            Ok(a) => a,
+            Err(e) => {
+                eprintln!("decode failed: {:#}", e);
+                payload.error = Some(format!("decode failed: {:#}", e));
+                ui.send_payload(payload);
+                return;
+            }
+        };
+        // With a dialogue mode, isolated over the whole decoded window, so speech detection
+        // listens to the dialogue too
+        let isolated = match dialogue_mode {
+            dialogue::DialogueMode::Off => None,
+            _ => match dialogue::isolate(&audio) {
+                Ok((d, info)) => {
+                    eprintln!("dialogue: {} source, {}", info.layout, info.method);
+                    payload.dialogue = Some(info);
+                    Some(d)
+                }
+                Err(e) => {
+                    eprintln!("dialogue isolation failed: {:#}", e);
+                    payload.warnings.push(format!("dialogue isolation failed: {:#}", e));
+                    None
+                }
+            },
+        };
+        // The speech onset feeds the sub offset estimate
+        let speech = vad::speech_near(isolated.as_ref().unwrap_or(&audio), decode_s, (src.line_s, src.line_e));
+        if let Ok(mut o) = offsets.lock() {
+            if let Some(lag) = speech.and_then(|(speech_s, _)| vad::onset_lag(speech_s, decode_s, src.line_s)) {
+                o.push(&src.media_path, payload.sub_delay, lag);
+            }
+            payload.sub_offset = o.estimate(&src.media_path, payload.sub_delay);
+        }
+        let (audio, isolated) = if src.auto_trim {
+            let (trimmed, trim) = vad::trim_to(&audio, decode_s, speech, (src.s, src.e), cut::DEFAULT_PAD_S);
+            match &trim {
+                Some(t) => eprintln!("auto-trim: speech {:.3}-{:.3}, cut {:.3}-{:.3} (was {:.3}-{:.3})", t.speech_s, t.speech_e, t.s, t.e, t.orig_s, t.orig_e),
+                None => eprintln!("auto-trim: no speech found; keeping {:.3}-{:.3}", src.s, src.e),
+            }
+            if let Some(t) = trim {
+                (payload.s, payload.e) = (t.s, t.e);
+            }
+            payload.trim = trim;
+            (trimmed, isolated.map(|d| vad::trim_to(&d, decode_s, speech, (src.s, src.e), cut::DEFAULT_PAD_S).0))
+        } else {
+            let slice = |a: &decode::DecodedAudio| a.slice(src.s - decode_s, src.e - decode_s);
+            (slice(&audio), isolated.as_ref().map(slice))
+        };
+        // F0 takes the dialogue; in Clip mode the clips are the dialogue too
+        let (audio, isolated) = match (dialogue_mode, isolated) {
+            (dialogue::DialogueMode::Clip, Some(d)) => (d, None),
+            (_, d) => (audio, d),
+        };
+        let analysed = isolated.as_ref().unwrap_or(&audio);
+        let lat = start_instant.elapsed().as_millis() as u64;
+        let stereo = audio.to_stereo();
+        let spec = wav::WavSpec { sample_rate: audio.sample_rate, channels: 2, format: wav::SampleFormat::Pcm16 };
+        let context = cut::ClipContext {
+            text: payload.text.clone(),
+            translation: payload.translation.clone(),
+            media: src.media_path.clone(),
+            ff_index: src.ff_index,
+            s: payload.s,
+            e: payload.e,
+            line_s: src.line_s,
+            line_e: src.line_e,
+            trim: payload.trim,
+            dialogue: payload.dialogue.clone(),
+            f0: None,
+        };
+        let write = |path: &Path, context: &cut::ClipContext| wav::write(path, &stereo, spec, &context.to_metadata(audio.sample_rate))
+            .map_err(|e| { eprintln!("clip write failed ({}): {:#}", path.display(), e); e });
+
+        thread::scope(|scope| {
+            let latest = scope.spawn(|| write(&latest_path, &context));
+            // The unique clip embeds the F0 summary, so it is written once F0 is done.
+            // Always rewritten: one left by an earlier run may carry other settings' F0
+            let f0_unique = scope.spawn(|| {
+                let start_f0 = Instant::now();
+                let (mono, sr) = analysis::analysis_mono(&analysed.samples, analysed.channels, analysed.sample_rate);
+                // F0 runs on the enhanced audio (kept as latest_enh.wav); a failed enhancement
+                // falls back to the original
+                let mut warning = None;
+                let enhanced = enhancer.as_deref().and_then(|en| match enhance::run(en, &mono, sr) {
+                    Ok((x, ms)) => Some((en.name(), x, ms)),
+                    Err(e) => {
+                        eprintln!("enhance failed: {:#}", e);
+                        warning = Some(format!("enhance failed: {:#}", e));
+                        None
+                    }
+                });
+                let f0 = analysis::summarize_f0(enhanced.as_ref().map_or(&mono, |(_, x, _)| x), sr, cut::DEFAULT_PAD_S as f32, method);
+                eprintln!(
+                    "f0: computed in {} ms; src median={:?} Hz voiced={:.0}% voicing={:.2}",
+                    start_f0.elapsed().as_millis(),
+                    f0.median_hz,
+                    f0.voiced_ratio * 100.0,
+                    f0.voicing
+                );
+                let written = write(&out_path, &cut::ClipContext { f0: Some(f0.clone()), ..context.clone() });
+                let enhancement = enhanced.and_then(|(method, x, ms)| {
+                    let path = enhance::enhanced_path(&latest_path);
+                    let meta = cut::ClipContext { f0: Some(f0.clone()), ..context.clone() }.to_metadata(sr);
+                    enhance::write_enhanced(&path, &x, sr, method, ms, &meta)
+                        .map_err(|e| eprintln!("enhanced clip write failed: {:#}", e))
+                        .ok()
+                });
+                (f0, written.is_ok(), enhancement, warning)
+            });
+
+            let (rms, peak) = analysis::rms_peak(&stereo);
+            let levels = loudness::measure_decoded(&audio);
+            eprintln!(
+                "decode latency ({}): {} ms; rms={:.4} peak={:.4} loudness={:?} LUFS true-peak={:.1} dBTP clipped={}",
+                audio.backend, lat, rms, peak, levels.integrated_lufs, levels.true_peak_dbtp, levels.clipped_samples
+            );
+            payload.latency_ms = lat;
+            payload.rms = rms;
+            payload.peak = peak;
+            // latest_norm.wav from the same buffer, before the levels go out
+            if let Some(target) = normalize_lufs {
+                let norm_path = loudness::normalized_path(&latest_path);
+                let meta = context.to_metadata(audio.sample_rate);
+                match loudness::write_normalized(&norm_path, &stereo, spec, &levels, target, &meta) {
+                    Ok(n) => payload.norm_src = n,
+                    Err(e) => eprintln!("normalise failed: {:#}", e),
+                }
+            }
+            payload.loudness_src = Some(levels);
+            if !latest.join().is_ok_and(|r| r.is_ok()) {
+                payload.error = Some("clip write failed".to_string());
+            }
+            ui.send_payload(payload.clone());
+
+            if let Ok((f0, unique_ok, enhancement, warning)) = f0_unique.join() {
+                payload.enhanced = enhancement;
+                payload.warnings.extend(warning);
+                payload.f0_src_median = f0.median_hz;
+                payload.voiced_src = Some(f0.voiced_ratio);
+                payload.voicing_src = Some(f0.voicing);
+                payload.f0_src_series = Some(f0.series);
+                if !unique_ok {
+                    payload.error = Some("clip write failed".to_string());
+                }
+                ui.send_payload(payload);
+            }
+        });
+    });
+}
+
+fn cleanup_old_clips(out_dir: &Path, keep: usize, exclude: &[&Path]) {
+    let dir = out_dir.to_path_buf();
+    let exclude: Vec<std::path::PathBuf> = exclude.iter().map(|p| p.to_path_buf()).collect();
+    thread::spawn(move || {
+        let Ok(read_dir) = std::fs::read_dir(&dir) else { return };
+        let mut entries: Vec<(std::path::PathBuf, std::time::SystemTime)> = Vec::new();
+        for e in read_dir.flatten() {
+            let path = e.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("wav") { continue; }
+            if exclude.iter().any(|ex| ex == &path) { continue; }
+            let file_name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+            // latest.wav, latest_mic.wav and their _norm copies are overwritten, never rotated
+            if file_name.to_ascii_lowercase().starts_with("latest") { continue; }
+            let Ok(meta) = e.metadata() else { continue };
+            let Ok(modified) = meta.modified() else { continue };
+            entries.push((path, modified));
+        }
+        // Sort newest first
+        entries.sort_by_key(|e| std::cmp::Reverse(e.1));
+        if entries.len() > keep {
+            for (path, _) in entries.into_iter().skip(keep) {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_selected_audio_ff_index() {
+        let tl = json!([
+            {"type": "audio", "selected": false, "ff-index": 1},
+            {"type": "video", "selected": true, "ff-index": 0},
+            {"type": "audio", "selected": true, "ff-index": 3},
+        ]);
+        assert_eq!(selected_audio_ff_index(&tl), Some(3));
+        assert_eq!(selected_audio_ff_index(&json!([])), None);
+    }
+
+    #[test]
+    fn test_translation_for_requires_overlap() {
+        let line = |t: &str, s: f64, e: f64| SubLine { text: Some(t.to_string()), translation: None, start: s, end: e };
+        let ja = line("おはよう", 10.0, 12.0);
+        assert_eq!(translation_for(&ja, Some(&line("Morning", 10.1, 12.2))).as_deref(), Some("Morning"));
+        // Stale translation from the previous line
+        assert_eq!(translation_for(&ja, Some(&line("Bye", 6.0, 9.0))), None);
+        // No secondary-sub-start/end: taken as-is
+        assert_eq!(translation_for(&ja, Some(&line("Hi", 0.0, 0.0))).as_deref(), Some("Hi"));
+        assert_eq!(translation_for(&ja, None), None);
+    }
+
+    #[test]
+    fn test_mic_take_levels_f0_and_normalised_copy() {
+        let dir = std::env::temp_dir().join(format!("mpvshadow_mic_take_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let latest_mic = dir.join("latest_mic.wav");
+        let tone: Vec<f32> = (0..48000).map(|i| (2.0 * std::f32::consts::PI * 150.0 * i as f32 / 48000.0).sin() * 0.1).collect();
+        let mut meta = wav::WavMetadata::default();
+        meta.set_info(b"ISFT", "Lavf60.16.100");
+        let spec = wav::WavSpec { sample_rate: 48000, channels: 1, format: wav::SampleFormat::Pcm16 };
+        wav::write(&latest_mic, &tone, spec, &meta).unwrap();
+        let take = mic_take("cut.wav", &latest_mic, &dir.join("take_mic.wav"), Some(-23.0), pitch::PitchMethod::Mpm);
+        assert!(take.loudness.is_some());
+        assert!(take.f0.and_then(|f| f.median_hz).is_some_and(|f| (f - 150.0).abs() < 3.0));
+        let norm = take.norm.expect("normalised copy");
+        let copy = wav::WavReader::open(Path::new(&norm.path)).unwrap().metadata;
+        assert_eq!(copy.info(b"ISFT"), Some("Lavf60.16.100"));
+        assert!(copy.info(b"ICMT").is_some_and(|c| c.starts_with("normalised to -23.0 LUFS")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod e2e;
@@ -0,0 +1,418 @@
+// End-to-end tests of the live loop against the in-process fake mpv (mock_mpv.rs): cuts,
+// navigation, delays, offsets and reconnects, with synthetic WAV media decoded in-process.
+// Unix only, as the mock listens on a Unix socket.
+
+use super::*;
+use crate::mock_mpv::{self, MockMpv};
+use serde_json::json;
+
+const WAIT: Duration = Duration::from_secs(5);
+
+// UiBridge whose wake-ups land on a channel the test can wait on
+fn test_bridge() -> (UiBridge, mpsc::Receiver<()>) {
+    let (tx, rx) = mpsc::channel();
+    (UiBridge::new(move || { let _ = tx.send(()); }), rx)
+}
+
+fn wait_payload<F: Fn(&UiPayload) -> bool>(ui: &UiBridge, rx: &mpsc::Receiver<()>, pred: F) -> Option<UiPayload> {
+    let deadline = Instant::now() + Duration::from_secs(20);
+    while Instant::now() < deadline {
+        let p = ui.payload.lock().unwrap().take();
+        if let Some(p) = p.filter(|p| pred(p)) { return Some(p); }
+        let _ = rx.recv_timeout(Duration::from_millis(100));
+    }
+    None
+}
+
+fn wait_status(ui: &UiBridge, rx: &mpsc::Receiver<()>, connected: bool) -> bool {
+    let deadline = Instant::now() + WAIT;
+    while Instant::now() < deadline {
+        if ui.status.lock().unwrap().as_ref().map(|s| s.connected) == Some(connected) { return true; }
+        let _ = rx.recv_timeout(Duration::from_millis(50));
+    }
+    false
+}
+
+fn scratch_dir(tag: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mpvshadow_e2e_{}_{}", tag, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn start(mock: &MockMpv, ui: &UiBridge, out_dir: &Path) -> thread::JoinHandle<()> {
+    start_with(mock, ui, LiveOptions { out_dir: out_dir.to_path_buf(), ..Default::default() })
+}
+
+fn start_with(mock: &MockMpv, ui: &UiBridge, opts: LiveOptions) -> thread::JoinHandle<()> {
+    let opts = LiveOptions { mic: false, reconnect: false, ..opts };
+    let (path, ui) = (mock.path(), ui.clone());
+    thread::spawn(move || run_analyzer(path, ui, Arc::new(Mutex::new(None)), opts))
+}
+
+fn is_set(c: &[Value], name: &str, v: Value) -> bool {
+    c.len() == 3 && c[0] == "set_property" && c[1] == name && c[2] == v
+}
+
+// Cut a line over 1.0 s of 200 Hz (0.5-1.5 s of 2 s, stereo) with `opts` and return the
+// scratch directory and the cut's last payload (the one with the F0)
+fn cut_tone_line(tag: &str, opts: LiveOptions) -> (PathBuf, UiPayload) {
+    let dir = scratch_dir(tag);
+    let media = dir.join("episode.wav");
+    mock_mpv::write_tone_wav(&media, 48000, 2, 200.0, 0.5, 1.0).unwrap();
+    let mock = MockMpv::start(tag).unwrap();
+    mock.load_media(&media, 2.0, 0);
+    let (ui, rx) = test_bridge();
+    let handle = start_with(&mock, &ui, LiveOptions { out_dir: dir.join("out"), ..opts });
+    assert!(mock.wait_observed("sub-text", WAIT));
+    mock.emit_sub_text("line", 0.5, 1.5);
+    mock.emit_client_message(&["cut_current_sub"]);
+    let p = wait_payload(&ui, &rx, |p| p.f0_src_median.is_some()).expect("f0 payload");
+    mock.disconnect();
+    handle.join().unwrap();
+    (dir, p)
+}
+
+#[test]
+fn test_cut_current_sub_end_to_end() {
+    let dir = scratch_dir("cut");
+    let media = dir.join("episode.wav");
+    // 0.5 s silence, 1.0 s of 200 Hz, 0.5 s silence
+    mock_mpv::write_tone_wav(&media, 48000, 2, 200.0, 0.5, 1.0).unwrap();
+    let out_dir = dir.join("out");
+
+    let mock = MockMpv::start("cut").unwrap();
+    mock.load_media(&media, 2.0, 0);
+    let (ui, rx) = test_bridge();
+    let handle = start(&mock, &ui, &out_dir);
+    assert!(mock.wait_connections(1, WAIT));
+    assert!(wait_status(&ui, &rx, true));
+    assert!(mock.wait_observed("sub-text", WAIT));
+
+    // No line seen yet: the trigger is ignored
+    mock.emit_client_message(&["cut_current_sub"]);
+    thread::sleep(Duration::from_millis(200));
+    assert!(!mock.commands().iter().any(|c| c[0] == "set_property"));
+
+    mock.emit_sub_text("こんにちは", 0.5, 1.5);
+    mock.emit_secondary_sub_text("Hello", 0.45, 1.55);
+    mock.emit_client_message(&["cut_current_sub"]);
+    let osd = mock.wait_command(WAIT, |c| c[0] == "show-text").expect("osd");
+    assert!(osd[1].as_str().unwrap().starts_with("cut 0.400–1.600"), "{:?}", osd);
+    let cmds = mock.commands();
+    assert!(cmds.iter().any(|c| c[0] == "get_property" && c[1] == "secondary-sub-end"));
+    let pos = |pred: &dyn Fn(&[Value]) -> bool| cmds.iter().position(|c| pred(c.as_array().unwrap()));
+    let pause = pos(&|c| is_set(c, "pause", json!(true))).expect("pause");
+    let seek = pos(&|c| is_set(c, "time-pos", json!(0.4))).expect("seek");
+    let resume = pos(&|c| is_set(c, "pause", json!(false))).expect("resume");
+    assert!(pause < seek && seek < resume);
+    assert!(mock.wait_observed("time-pos", WAIT));
+
+    // Playback reaching the end of the window pauses again and stops observing time-pos
+    mock.clear_commands();
+    mock.emit_property_change("time-pos", json!(1.0));
+    mock.emit_property_change("time-pos", json!(1.59));
+    assert!(mock.wait_command(WAIT, |c| is_set(c, "pause", json!(true))).is_some());
+    assert!(mock.wait_command(WAIT, |c| c[0] == "unobserve_property" && c[1] == OBSERVE_TIME_POS).is_some());
+
+    // One in-process decode feeds both clips and the analysis; the files are
+    // complete by the time the first payload arrives
+    let clip = cut::clip_path(&out_dir, &media.to_string_lossy(), 0.4, 1.6);
+    let p = wait_payload(&ui, &rx, |_| true).expect("payload");
+    assert_eq!(p.error, None);
+    assert!(p.warnings.is_empty(), "{:?}", p.warnings);
+    assert_eq!(p.text.as_deref(), Some("こんにちは"));
+    assert_eq!(p.translation.as_deref(), Some("Hello"));
+    assert_eq!(p.ff_index, Some(0));
+    assert_eq!(p.out_path, clip.to_string_lossy());
+    assert!(p.mic_out_path.is_none());
+    // 1.0 s of tone in 1.2 s: the padding is gated out of the integrated loudness
+    let l = p.loudness_src.as_ref().expect("loudness");
+    assert!(l.integrated_lufs.is_some() && l.true_peak_dbtp < 0.0 && l.clipped_samples == 0, "{:?}", l);
+    assert!(!l.short_term.is_empty());
+    assert!(out_dir.join("latest.wav").exists());
+    let p = if p.f0_src_median.is_some() { p } else { wait_payload(&ui, &rx, |p| p.f0_src_median.is_some()).expect("f0 payload") };
+    let f0 = p.f0_src_median.unwrap();
+    assert!((f0 - 200.0).abs() < 5.0, "f0 {}", f0);
+
+    // Both clips carry the line; the unique one also has the F0 summary
+    let context = |path: &Path| {
+        let meta = wav::WavReader::open(path).unwrap().metadata;
+        assert_eq!(meta.cues.len(), 1, "line marker");
+        let (_, context) = meta.chunks.iter().find(|(id, _)| *id == cut::CONTEXT_CHUNK_ID).expect("context chunk");
+        serde_json::from_slice::<Value>(context).unwrap()
+    };
+    let (unique, latest) = (context(&clip), context(&out_dir.join("latest.wav")));
+    assert_eq!(unique["text"], "こんにちは");
+    assert_eq!(latest["translation"], "Hello");
+    assert!(unique["f0"]["median_hz"].is_number() && latest["f0"].is_null());
+
+    mock.disconnect();
+    handle.join().unwrap();
+    assert_eq!(ui.status.lock().unwrap().as_ref().map(|s| s.connected), Some(false));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_normalized_playback_copy() {
+    let (dir, p) = cut_tone_line("norm", LiveOptions { normalize_lufs: Some(-20.0), ..Default::default() });
+    let l = p.loudness_src.as_ref().expect("loudness");
+    // The playback copy is normalised; latest.wav itself is untouched
+    let n = p.norm_src.as_ref().expect("normalised copy");
+    assert_eq!(Path::new(&n.path), dir.join("out").join("latest_norm.wav"));
+    let (mono, sr) = wav::read_wav_mono(Path::new(&n.path), None).unwrap();
+    // Read back as one channel of identical L/R: 3 LU under the stereo measurement
+    let ln = loudness::measure(&mono, sr, &[1.0]);
+    let expected = l.integrated_lufs.unwrap() + n.gain_db - 3.01;
+    assert!((ln.integrated_lufs.unwrap() - expected).abs() < 0.2, "{:?} {:?}", n, ln);
+    assert!(ln.true_peak_dbtp <= -0.9);
+    let comment = wav::WavReader::open(Path::new(&n.path)).unwrap().metadata.info(b"ICMT").map(str::to_string);
+    assert!(comment.is_some_and(|c| c.ends_with(&format!("normalised to -20.0 LUFS ({:+.1} dB)", n.gain_db))));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_enhanced_source_feeds_f0() {
+    let (dir, p) = cut_tone_line("enh", LiveOptions { enhancer: Some(Arc::new(enhance::Wiener)), ..Default::default() });
+    assert!(p.f0_src_median.is_some_and(|f| (f - 200.0).abs() < 5.0), "{:?}", p.f0_src_median);
+    // F0 came from the enhanced copy, which is there to play
+    let enh = p.enhanced.as_ref().expect("enhanced");
+    assert_eq!(Path::new(&enh.path), dir.join("out").join("latest_enh.wav"));
+    assert!(wav::WavReader::open(Path::new(&enh.path)).unwrap().metadata.info(b"ICMT").is_some_and(|c| c.ends_with("enhanced (wiener)")));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_enhancement_failure_is_a_warning() {
+    let (dir, p) = cut_tone_line("enh_fail", LiveOptions { enhancer: Some(Arc::new(enhance::FailingEnhancer)), ..Default::default() });
+    // F0 falls back to the original audio, and the UI is told why there is no enhanced copy
+    assert!(p.enhanced.is_none() && p.error.is_none());
+    assert_eq!(p.warnings.len(), 1);
+    assert!(p.warnings[0].contains("model error"), "{:?}", p.warnings);
+    assert!(p.f0_src_median.is_some_and(|f| (f - 200.0).abs() < 5.0), "{:?}", p.f0_src_median);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_dialogue_analysis_listens_to_the_centre() {
+    let (dir, p) = cut_tone_line("dialogue", LiveOptions { dialogue: dialogue::DialogueMode::Analysis, ..Default::default() });
+    // Speech detection and F0 listened to the centre of the stereo mix
+    assert_eq!(p.dialogue.as_ref().map(|d| (d.layout.as_str(), d.method)), Some(("stereo", "coherence")));
+    assert!(p.f0_src_median.is_some_and(|f| (f - 200.0).abs() < 5.0), "{:?}", p.f0_src_median);
+    // The clip's context notes the layout and the method
+    let meta = wav::WavReader::open(&dir.join("out").join("latest.wav")).unwrap().metadata;
+    let (_, context) = meta.chunks.iter().find(|(id, _)| *id == cut::CONTEXT_CHUNK_ID).expect("context chunk");
+    assert_eq!(serde_json::from_slice::<Value>(context).unwrap()["dialogue"]["method"], "coherence");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_auto_trim_moves_cut_to_speech() {
+    let dir = scratch_dir("trim");
+    let media = dir.join("episode.wav");
+    // Tone (the "speech") at 1.0-2.0 s; the subtitle claims 0.7-1.6
+    mock_mpv::write_tone_wav(&media, 48000, 1, 200.0, 1.0, 1.0).unwrap();
+    let out_dir = dir.join("out");
+    let mock = MockMpv::start("trim").unwrap();
+    mock.load_media(&media, 3.0, 0);
+    let (ui, rx) = test_bridge();
+    let handle = start_with(&mock, &ui, LiveOptions { out_dir: out_dir.clone(), auto_trim: true, ..Default::default() });
+    assert!(mock.wait_observed("sub-text", WAIT));
+    mock.emit_sub_text("line", 0.7, 1.6);
+    mock.emit_client_message(&["cut_current_sub"]);
+    let p = wait_payload(&ui, &rx, |p| p.loudness_src.is_some()).expect("levels payload");
+    let t = p.trim.expect("trim");
+    assert!((t.orig_s - 0.6).abs() < 1e-9 && (t.orig_e - 1.7).abs() < 1e-9, "{:?}", t);
+    assert!((t.speech_s - 1.0).abs() < 0.03 && (t.speech_e - 2.0).abs() < 0.03, "{:?}", t);
+    assert_eq!((p.s, p.e), (t.s, t.e));
+    // Named after the subtitle window, holding the speech window
+    assert!(p.out_path.ends_with("episode_600_1700.wav"), "{}", p.out_path);
+    let (mono, sr) = wav::read_wav_mono(&out_dir.join("latest.wav"), None).unwrap();
+    assert!(((mono.len() as f64 / sr as f64) - (t.e - t.s)).abs() < 1e-3);
+    mock.disconnect();
+    handle.join().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_sub_offset_estimate_and_apply() {
+    let dir = scratch_dir("offset");
+    let media = dir.join("episode.wav");
+    // Speech at 1.0-2.0 s, subtitle at 0.7-1.6: the subs are 0.3 s early
+    mock_mpv::write_tone_wav(&media, 48000, 1, 200.0, 1.0, 1.0).unwrap();
+    let mock = MockMpv::start("offset").unwrap();
+    mock.load_media(&media, 3.0, 0);
+    let (ui, rx) = test_bridge();
+    let handle = start(&mock, &ui, &dir.join("out"));
+    assert!(mock.wait_observed("sub-text", WAIT));
+    mock.emit_sub_text("line", 0.7, 1.6);
+    mock.emit_client_message(&["apply_sub_offset"]);
+    let osd = mock.wait_command(WAIT, |c| c[0] == "show-text").expect("osd");
+    assert_eq!(osd[1], "no sub offset estimate yet");
+
+    let mut last = None;
+    for n in 1..=3 {
+        mock.emit_client_message(&["cut_current_sub"]);
+        // The cut's last payload: levels, offset and F0
+        let p = wait_payload(&ui, &rx, |p| p.sub_offset.is_some_and(|o| o.cuts == n) && p.f0_src_median.is_some()).expect("offset payload");
+        // The clip itself keeps the subtitle window
+        assert!((p.s - 0.6).abs() < 1e-9 && (p.e - 1.7).abs() < 1e-9 && p.trim.is_none(), "{:?}", (p.s, p.e));
+        last = Some(p);
+    }
+    let cut = last.unwrap();
+    let o = cut.sub_offset.unwrap();
+    assert!(o.stable && (o.offset - 0.3).abs() < 0.03, "{:?}", o);
+
+    // The mic take, finishing after the pipeline, keeps the estimate (and the Apply button)
+    let out_dir = dir.join("out");
+    let (latest_mic, mic_out) = (out_dir.join("latest_mic.wav"), out_dir.join("take_mic.wav"));
+    mock_mpv::write_tone_wav(&latest_mic, 48000, 1, 150.0, 0.2, 1.0).unwrap();
+    ui.send_mic(mic_take(&cut.out_path, &latest_mic, &mic_out, None, pitch::PitchMethod::Mpm));
+    let p = wait_payload(&ui, &rx, |p| p.loudness_mic.is_some()).expect("mic payload");
+    assert_eq!(p.sub_offset, Some(o));
+    // The measured decode latency and levels are not reset either
+    assert!(cut.rms > 0.0 && cut.peak > 0.0);
+    assert_eq!((p.latency_ms, p.rms, p.peak), (cut.latency_ms, cut.rms, cut.peak));
+    assert_eq!(p.mic_out_path.as_deref(), Some(mic_out.to_string_lossy().as_ref()));
+    // The take's own F0 sits next to the source's
+    assert!(p.f0_mic_median.is_some_and(|f| (f - 150.0).abs() < 3.0), "{:?}", p.f0_mic_median);
+    assert_eq!(p.f0_src_median, cut.f0_src_median);
+    // A take of another cut is not merged
+    ui.send_mic(mic_take("other.wav", &latest_mic, &mic_out, None, pitch::PitchMethod::Mpm));
+    assert!(ui.payload.lock().unwrap().is_none());
+
+    // The UI action sets it in mpv
+    mock.clear_commands();
+    ui.apply_sub_offset();
+    let set = mock.wait_command(WAIT, |c| c[0] == "set_property" && c[1] == "sub-delay").expect("sub-delay");
+    assert!((set[2].as_f64().unwrap() - 0.3).abs() < 0.03, "{:?}", set);
+    let osd = mock.wait_command(WAIT, |c| c[0] == "show-text").expect("osd");
+    assert!(osd[1].as_str().unwrap().starts_with(&format!("sub-delay {:+.3} s", set[2].as_f64().unwrap())), "{:?}", osd);
+
+    mock.disconnect();
+    handle.join().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_cut_window_follows_delays_and_speed() {
+    let dir = scratch_dir("delays");
+    let media = dir.join("episode.wav");
+    mock_mpv::write_tone_wav(&media, 48000, 1, 200.0, 0.5, 1.0).unwrap();
+    let mock = MockMpv::start("delays").unwrap();
+    mock.load_media(&media, 2.0, 0);
+    mock.set_property("sub-delay", json!(0.3));
+    let (ui, _rx) = test_bridge();
+    let handle = start(&mock, &ui, &dir.join("out"));
+    assert!(mock.wait_observed("speed", WAIT));
+    // Changes after connecting arrive as property-change events
+    mock.set_property("audio-delay", json!(0.1));
+    mock.emit_property_change("audio-delay", json!(0.1));
+    mock.set_property("speed", json!(2.0));
+    mock.emit_property_change("speed", json!(2.0));
+
+    mock.emit_sub_text("line", 0.5, 1.5);
+    mock.emit_client_message(&["cut_current_sub"]);
+    // Shown at 0.8-1.8 (+pad 0.7-1.9); heard from audio 0.1 s earlier
+    let osd = mock.wait_command(WAIT, |c| c[0] == "show-text").expect("osd");
+    assert!(osd[1].as_str().unwrap().starts_with("cut 0.600–1.800"), "{:?}", osd);
+    let near = |v: &Value, x: f64| v.as_f64().is_some_and(|v| (v - x).abs() < 1e-6);
+    assert!(mock.wait_command(WAIT, |c| c[0] == "set_property" && c[1] == "time-pos" && near(&c[2], 0.7)).is_some());
+
+    mock.clear_commands();
+    mock.emit_property_change("time-pos", json!(1.85));
+    assert!(mock.wait_command(Duration::from_millis(300), |c| is_set(c, "pause", json!(true))).is_none());
+    mock.emit_property_change("time-pos", json!(1.87));
+    assert!(mock.wait_command(WAIT, |c| is_set(c, "pause", json!(true))).is_some());
+
+    mock.disconnect();
+    handle.join().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_line_navigation_and_loop() {
+    let dir = scratch_dir("nav");
+    let mock = MockMpv::start("nav").unwrap();
+    mock.load_media(&dir.join("episode.mkv"), 10.0, 1);
+    mock.set_subtitles(&[(1.0, 2.0, "one"), (3.0, 4.0, "two"), (5.0, 6.0, "three")]);
+    let (ui, _rx) = test_bridge();
+    let handle = start(&mock, &ui, &dir);
+    assert!(mock.wait_observed("sub-text", WAIT));
+    let near = |v: &Value, x: f64| v.as_f64().is_some_and(|v| (v - x).abs() < 1e-6);
+    let seeked_to = |x: f64| mock.wait_command(WAIT, |c| c[0] == "set_property" && c[1] == "time-pos" && near(&c[2], x)).is_some();
+
+    // Nothing on screen yet: next goes to the first line and plays it from the padded start
+    mock.emit_client_message(&["next_line"]);
+    assert!(seeked_to(0.9));
+    assert!(mock.wait_command(WAIT, |c| is_set(c, "pause", json!(false))).is_some());
+
+    // Next from inside "one" (even while still in its pre-roll) lands on "two"
+    mock.clear_commands();
+    mock.emit_client_message(&["next_line"]);
+    assert!(mock.wait_command(WAIT, |c| c[0] == "sub-seek" && c[1] == 1).is_some());
+    assert!(seeked_to(2.9));
+
+    mock.clear_commands();
+    mock.emit_client_message(&["prev_line"]);
+    assert!(mock.wait_command(WAIT, |c| c[0] == "sub-seek" && c[1] == -1).is_some());
+    assert!(seeked_to(0.9));
+
+    // Loop "one" twice: the first pass end seeks back, the second pauses
+    mock.clear_commands();
+    mock.emit_client_message(&["loop_line", "2"]);
+    assert!(seeked_to(0.9));
+    mock.clear_commands();
+    mock.emit_property_change("time-pos", json!(2.09));
+    assert!(seeked_to(0.9));
+    assert!(!mock.commands().iter().any(|c| is_set(c.as_array().unwrap(), "pause", json!(true))));
+    // A stale position from before the seek does not count as a second pass
+    mock.emit_property_change("time-pos", json!(2.1));
+    mock.emit_property_change("time-pos", json!(1.5));
+    assert!(mock.wait_command(Duration::from_millis(200), |c| is_set(c, "pause", json!(true))).is_none());
+    mock.emit_property_change("time-pos", json!(2.09));
+    assert!(mock.wait_command(WAIT, |c| is_set(c, "pause", json!(true))).is_some());
+
+    mock.clear_commands();
+    mock.emit_client_message(&["replay_line"]);
+    assert!(seeked_to(0.9));
+    assert!(mock.wait_command(WAIT, |c| is_set(c, "pause", json!(false))).is_some());
+    assert!(!mock.commands().iter().any(|c| c[0] == "show-text"));
+
+    mock.disconnect();
+    handle.join().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_reconnect_starts_a_clean_session() {
+    let dir = scratch_dir("reconnect");
+    let mock = MockMpv::start("reconnect").unwrap();
+    let (ui, rx) = test_bridge();
+    let opts = LiveOptions { out_dir: dir.clone(), mic: false, reconnect: true, ..Default::default() };
+    let (path, ui_an, stop) = (mock.path(), ui.clone(), Arc::clone(&opts.stop));
+    let handle = thread::spawn(move || run_analyzer(path, ui_an, Arc::new(Mutex::new(None)), opts));
+
+    assert!(mock.wait_connections(1, WAIT));
+    assert!(mock.wait_observed("sub-text", WAIT));
+    mock.emit_sub_text("first", 1.0, 2.0);
+    assert!(mock.wait_command(WAIT, |c| c[0] == "get_property" && c[1] == "sub-end").is_some());
+    // mpv restarts with nothing on screen
+    mock.set_property("sub-text", Value::Null);
+    mock.disconnect();
+    assert!(mock.wait_connections(2, WAIT));
+    assert!(wait_status(&ui, &rx, true));
+    assert!(mock.wait_observed("sub-text", WAIT));
+    // The line from the previous session must not be cut
+    mock.clear_commands();
+    mock.emit_client_message(&["cut_current_sub"]);
+    thread::sleep(Duration::from_millis(200));
+    assert!(!mock.commands().iter().any(|c| c[0] == "set_property" || c[0] == "show-text"));
+
+    // Stopped, it returns once mpv goes away instead of reconnecting
+    stop.store(true, Ordering::Relaxed);
+    mock.disconnect();
+    handle.join().unwrap();
+    assert_eq!(ui.status.lock().unwrap().as_ref().map(|s| s.connected), Some(false));
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,308 @@
+// Whole-clip loudness after ITU-R BS.1770 / EBU R128: K-weighted, gated integrated
+// loudness, a short-term contour, true peak and a count of clipped samples.
+
+use std::path::Path;
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{analysis, cli, wav};
+use crate::biquad::Biquad;
+use crate::decode::{self, DecodedAudio};
+
+// Gating blocks: 400 ms, 75% overlap; short-term window: 3 s; both advance in 100 ms steps
+const STEP_S: f64 = 0.1;
+const BLOCK_STEPS: usize = 4;
+const SHORT_TERM_STEPS: usize = 30;
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+// Reported dB values never go below this (digital silence)
+const FLOOR_DB: f32 = -120.0;
+// |x| at or above this counts as clipped (16-bit full scale is 32767/32768)
+const CLIP_LEVEL: f32 = 0.9999;
+// Normalised copies keep their true peak at or below this, even if the target is missed
+const PEAK_CEILING_DBTP: f32 = -1.0;
+// True-peak interpolation: 4x oversampling, 12 taps per phase
+const OVERSAMPLE: usize = 4;
+const TAPS_PER_PHASE: usize = 12;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Loudness {
+    // Gated integrated loudness; None if the clip is under 400 ms or below the absolute gate
+    pub integrated_lufs: Option<f32>,
+    // Loudest short-term value
+    pub short_term_max_lufs: Option<f32>,
+    // Inter-sample peak (4x oversampled), dBTP
+    pub true_peak_dbtp: f32,
+    pub sample_peak_dbfs: f32,
+    pub clipped_samples: usize,
+    // Short-term loudness every 100 ms (LUFS, at most 64 points, floored at -70 for drawing).
+    // The first 3 s use the audio available so far.
+    pub short_term: Vec<f32>,
+}
+
+// Loudness-normalised playback copy of a clip; the original file is left as recorded
+#[derive(Debug, Clone, Serialize)]
+pub struct Normalization {
+    pub target_lufs: f32,
+    // Gain applied to the copy; less than target - integrated when the peak ceiling limits it
+    pub gain_db: f32,
+    pub path: String,
+}
+
+// `--normalize <LUFS>` (e.g. -23 or -16); Ok(None) when not given
+pub fn parse_target(args: &[String]) -> Result<Option<f32>> {
+    match cli::parse_f64(args, "--normalize")? {
+        Some(t) if (-70.0..=0.0).contains(&t) => Ok(Some(t as f32)),
+        Some(t) => anyhow::bail!("--normalize must be between -70 and 0 LUFS, got {}", t),
+        None => Ok(None),
+    }
+}
+
+// Gain (dB) that brings the clip to `target_lufs` without pushing its true peak over
+// -1 dBTP; None for clips without an integrated loudness (too short or silent)
+pub fn normalize_gain_db(l: &Loudness, target_lufs: f32) -> Option<f32> {
+    let integrated = l.integrated_lufs?;
+    Some((target_lufs - integrated).min(PEAK_CEILING_DBTP - l.true_peak_dbtp))
+}
+
+// Write `samples` scaled to the target next to the original; returns what was applied
+pub fn write_normalized(
+    path: &Path,
+    samples: &[f32],
+    spec: wav::WavSpec,
+    l: &Loudness,
+    target_lufs: f32,
+    meta: &wav::WavMetadata,
+) -> Result<Option<Normalization>> {
+    let Some(gain_db) = normalize_gain_db(l, target_lufs) else { return Ok(None) };
+    let g = 10f32.powf(gain_db / 20.0);
+    let scaled: Vec<f32> = samples.iter().map(|x| x * g).collect();
+    // Say in the comment that this copy is not at source level
+    let mut meta = meta.clone();
+    meta.append_comment(&format!("normalised to {:.1} LUFS ({:+.1} dB)", target_lufs, gain_db));
+    wav::write(path, &scaled, spec, &meta)?;
+    Ok(Some(Normalization { target_lufs, gain_db, path: path.to_string_lossy().to_string() }))
+}
+
+// `<stem>_norm.wav` beside a clip
+pub fn normalized_path(clip: &Path) -> std::path::PathBuf {
+    let stem = clip.file_stem().and_then(|s| s.to_str()).unwrap_or("clip");
+    clip.with_file_name(format!("{}_norm.wav", stem))
+}
+
+// Loudness of a decoded window, with BS.1770 channel weights (LFE ignored, surrounds +1.5 dB)
+pub fn measure_decoded(audio: &DecodedAudio) -> Loudness {
+    let weights: Vec<f64> = if audio.channels <= 2 {
+        vec![1.0; audio.channels.max(1) as usize]
+    } else {
+        audio.channel_roles().iter().map(|&r| match r {
+            decode::SPEAKER_LOW_FREQUENCY => 0.0,
+            decode::SPEAKER_FRONT_LEFT | decode::SPEAKER_FRONT_RIGHT | decode::SPEAKER_FRONT_CENTER => 1.0,
+            _ => 1.41,
+        }).collect()
+    };
+    measure(&audio.samples, audio.sample_rate, &weights)
+}
+
+// Interleaved samples; `weights` has one entry per channel
+pub fn measure(samples: &[f32], sample_rate: u32, weights: &[f64]) -> Loudness {
+    let ch = weights.len().max(1);
+    let frames = samples.len() / ch;
+
+    // Weighted mean-square energy per 100 ms step (a trailing partial step is dropped)
+    let step_len = ((sample_rate as f64 * STEP_S).round() as usize).max(1);
+    let mut filters: Vec<KWeighting> = (0..ch).map(|_| KWeighting::new(sample_rate as f64)).collect();
+    let mut steps: Vec<f64> = Vec::with_capacity(frames / step_len);
+    let mut acc = 0.0f64;
+    for (i, frame) in samples.chunks_exact(ch).enumerate() {
+        for ((&x, f), &w) in frame.iter().zip(filters.iter_mut()).zip(weights) {
+            let y = f.process(x as f64);
+            acc += w * y * y;
+        }
+        if (i + 1) % step_len == 0 {
+            steps.push(acc / step_len as f64);
+            acc = 0.0;
+        }
+    }
+
+    let blocks: Vec<f64> = steps.windows(BLOCK_STEPS).map(mean).collect();
+    let integrated_lufs = gated_loudness(&blocks).map(|l| l as f32);
+
+    let short_term_raw: Vec<f64> = (0..steps.len())
+        .map(|i| lufs(mean(&steps[(i + 1).saturating_sub(SHORT_TERM_STEPS)..=i])))
+        .collect();
+    let short_term_max_lufs = short_term_raw.iter().cloned().fold(None, |m: Option<f64>, l| {
+        if l.is_finite() { Some(m.map_or(l, |m| m.max(l))) } else { m }
+    }).map(|l| l as f32);
+    let short_term: Vec<f32> = short_term_raw.iter().map(|&l| l.max(ABSOLUTE_GATE_LUFS) as f32).collect();
+
+    let (_, sample_peak) = analysis::rms_peak(samples);
+    let true_peak = true_peak(samples, ch).max(sample_peak);
+    let clipped_samples = samples.iter().filter(|x| x.abs() >= CLIP_LEVEL).count();
+
+    Loudness {
+        integrated_lufs,
+        short_term_max_lufs,
+        true_peak_dbtp: to_db(true_peak),
+        sample_peak_dbfs: to_db(sample_peak),
+        clipped_samples,
+        short_term: analysis::downsample_series(&short_term, 64),
+    }
+}
+
+fn mean(v: &[f64]) -> f64 {
+    if v.is_empty() { 0.0 } else { v.iter().sum::<f64>() / v.len() as f64 }
+}
+
+fn lufs(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+fn to_db(amplitude: f32) -> f32 {
+    (20.0 * amplitude.log10()).max(FLOOR_DB)
+}
+
+// Two-stage gating: drop blocks under -70 LUFS, then those 10 LU under the mean of the rest
+fn gated_loudness(blocks: &[f64]) -> Option<f64> {
+    let above_abs: Vec<f64> = blocks.iter().cloned().filter(|&z| lufs(z) > ABSOLUTE_GATE_LUFS).collect();
+    if above_abs.is_empty() { return None; }
+    let relative_gate = lufs(mean(&above_abs)) + RELATIVE_GATE_LU;
+    let gated: Vec<f64> = above_abs.into_iter().filter(|&z| lufs(z) > relative_gate).collect();
+    if gated.is_empty() { None } else { Some(lufs(mean(&gated))) }
+}
+
+// Largest interpolated |x| between samples, per channel, with a Hann-windowed sinc
+fn true_peak(samples: &[f32], ch: usize) -> f32 {
+    let half = TAPS_PER_PHASE as isize / 2;
+    // kernel[p][k]: weight of x[i + k - half + 1] for the point p/OVERSAMPLE after x[i]
+    let kernel: Vec<Vec<f32>> = (1..OVERSAMPLE).map(|p| {
+        let frac = p as f64 / OVERSAMPLE as f64;
+        (0..TAPS_PER_PHASE as isize).map(|k| {
+            let t = (k - half + 1) as f64 - frac;
+            let sinc = if t.abs() < 1e-9 { 1.0 } else { (std::f64::consts::PI * t).sin() / (std::f64::consts::PI * t) };
+            let w = 0.5 + 0.5 * (std::f64::consts::PI * t / (half as f64 + 1.0)).cos();
+            (sinc * w) as f32
+        }).collect()
+    }).collect();
+    let frames = samples.len() / ch.max(1);
+    let mut peak = 0f32;
+    for c in 0..ch {
+        let at = |i: isize| if i >= 0 && (i as usize) < frames { samples[i as usize * ch + c] } else { 0.0 };
+        for i in 0..frames as isize {
+            for taps in &kernel {
+                let mut y = 0f32;
+                for (k, &h) in taps.iter().enumerate() {
+                    y += h * at(i + k as isize - half + 1);
+                }
+                peak = peak.max(y.abs());
+            }
+        }
+    }
+    peak
+}
+
+// BS.1770 K-weighting: high-shelf "head" filter then the RLB high-pass, recomputed for
+// any sample rate (same derivation as libebur128)
+struct KWeighting {
+    stages: [Biquad; 2],
+}
+
+impl KWeighting {
+    fn new(fs: f64) -> Self {
+        let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (std::f64::consts::PI * f0 / fs).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (std::f64::consts::PI * f0 / fs).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let highpass = Biquad::new([1.0, -2.0, 1.0], [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0]);
+        Self { stages: [shelf, highpass] }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        self.stages.iter_mut().fold(x, |x, s| s.process(x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(sr: u32, hz: f32, amp: f32, secs: f32) -> Vec<f32> {
+        (0..(sr as f32 * secs) as usize)
+            .map(|i| amp * (2.0 * std::f32::consts::PI * hz * i as f32 / sr as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_1khz_reference_level() {
+        // EBU Tech 3341: a 1 kHz sine at -18 dBFS on both stereo channels reads -18 LUFS
+        // (a single mono channel reads 3 LU lower)
+        let amp = 10f32.powf(-18.0 / 20.0);
+        let mono = sine(48000, 1000.0, amp, 4.0);
+        let stereo: Vec<f32> = mono.iter().flat_map(|&x| [x, x]).collect();
+        let l = measure(&stereo, 48000, &[1.0, 1.0]);
+        let i = l.integrated_lufs.unwrap();
+        assert!((i + 18.0).abs() < 0.1, "integrated {}", i);
+        assert!((l.short_term_max_lufs.unwrap() + 18.0).abs() < 0.1);
+        assert!((l.true_peak_dbtp + 18.0).abs() < 0.2, "true peak {}", l.true_peak_dbtp);
+        assert_eq!(l.clipped_samples, 0);
+        let m = measure(&mono, 44100, &[1.0]);
+        assert!(m.integrated_lufs.is_some());
+    }
+
+    #[test]
+    fn test_gating_ignores_silence_and_short_clips() {
+        let amp = 10f32.powf(-20.0 / 20.0);
+        let mut x = vec![0.0f32; 48000 * 2];
+        x.extend(sine(48000, 1000.0, amp, 1.0));
+        let l = measure(&x, 48000, &[1.0]);
+        // Silence is gated away (ungated, the 3 s would read about -27.8)
+        assert!((l.integrated_lufs.unwrap() + 23.0).abs() < 1.0, "{:?}", l.integrated_lufs);
+        assert_eq!(l.short_term.first().copied(), Some(-70.0));
+        assert!(measure(&vec![0.0; 48000], 48000, &[1.0]).integrated_lufs.is_none());
+        assert!(measure(&sine(48000, 1000.0, 0.5, 0.3), 48000, &[1.0]).integrated_lufs.is_none());
+    }
+
+    #[test]
+    fn test_normalize_gain_respects_peak_ceiling() {
+        let quiet = measure(&sine(48000, 1000.0, 0.05, 1.0), 48000, &[1.0]);
+        let g = normalize_gain_db(&quiet, -23.0).unwrap();
+        assert!((quiet.integrated_lufs.unwrap() + g + 23.0).abs() < 0.01);
+        // Very peaky relative to its loudness: the -1 dBTP ceiling wins over the target
+        let mut spiky = vec![0.0f32; 48000];
+        spiky.extend(sine(48000, 1000.0, 0.01, 1.0));
+        spiky[100] = 0.9;
+        let l = measure(&spiky, 48000, &[1.0]);
+        let g = normalize_gain_db(&l, -14.0).unwrap();
+        assert!((l.true_peak_dbtp + g - PEAK_CEILING_DBTP).abs() < 0.01);
+        assert!(normalize_gain_db(&measure(&[0.0; 4800], 48000, &[1.0]), -23.0).is_none());
+    }
+
+    #[test]
+    fn test_parse_target() {
+        let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(parse_target(&args(&["--normalize", "-16"])).unwrap(), Some(-16.0));
+        assert_eq!(parse_target(&args(&[])).unwrap(), None);
+        assert!(parse_target(&args(&["--normalize=6"])).is_err());
+    }
+
+    #[test]
+    fn test_true_peak_and_clipping() {
+        // fs/4 sine with a 45° phase: samples at ±0.707·A, peaks between them at ±A
+        let x: Vec<f32> = (0..4800)
+            .map(|i| (std::f32::consts::FRAC_PI_2 * i as f32 + std::f32::consts::FRAC_PI_4).sin())
+            .collect();
+        let l = measure(&x, 48000, &[1.0]);
+        assert!((l.sample_peak_dbfs + 3.01).abs() < 0.05);
+        assert!(l.true_peak_dbtp > -0.5, "true peak {}", l.true_peak_dbtp);
+        let clipped = measure(&[1.0, -1.0, 0.5, 1.2], 48000, &[1.0]);
+        assert_eq!(clipped.clipped_samples, 3);
+    }
+}
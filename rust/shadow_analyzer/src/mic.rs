@@ -0,0 +1,169 @@
+// Microphone capture through ffmpeg: device discovery and fixed-length recording.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+#[cfg(all(windows, feature = "gui"))]
+use windows::Win32::Media::Audio::{DEVICE_STATE_ACTIVE, EDataFlow, IMMDeviceCollection, IMMDeviceEnumerator};
+#[cfg(all(windows, feature = "gui"))]
+use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_MULTITHREADED};
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct MicDeviceInfo { pub id: String, pub name: String }
+
+// ffmpeg capture input for microphones: DirectShow on Windows, PulseAudio/PipeWire elsewhere
+#[cfg(windows)]
+const MIC_INPUT_FORMAT: &str = "dshow";
+#[cfg(not(windows))]
+const MIC_INPUT_FORMAT: &str = "pulse";
+
+// Prefer DirectShow device names (what ffmpeg expects), fallback to WASAPI GUIDs
+#[cfg(windows)]
+fn list_mic_devices_dshow() -> Option<Vec<MicDeviceInfo>> {
+    let output = Command::new("ffmpeg")
+        .args(["-hide_banner", "-f", "dshow", "-list_devices", "true", "-i", "dummy"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .ok()?;
+    let stderr_text = String::from_utf8_lossy(&output.stderr);
+    let mut out: Vec<MicDeviceInfo> = Vec::new();
+    for line in stderr_text.lines() {
+        // Skip alternative moniker lines; we want human-friendly names
+        if line.contains("Alternative name") { continue; }
+        // We only care about audio device entries
+        if !line.contains("(audio)") { continue; }
+        // Extract quoted device name
+        if let Some(start) = line.find('"') {
+            if let Some(end_rel) = line[start+1..].find('"') {
+                let name = &line[start+1..start+1+end_rel];
+                if !name.is_empty() {
+                    let id = format!("audio={}", name);
+                    out.push(MicDeviceInfo { id, name: name.to_string() });
+                }
+            }
+        }
+    }
+    if out.is_empty() { None } else { Some(out) }
+}
+
+#[cfg(all(windows, feature = "gui"))]
+pub fn list_mic_devices() -> Vec<MicDeviceInfo> {
+    if let Some(list) = list_mic_devices_dshow() { return list; }
+    unsafe {
+        let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+        let enumerator: IMMDeviceEnumerator = CoCreateInstance(&windows::Win32::Media::Audio::MMDeviceEnumerator, None, CLSCTX_ALL).unwrap();
+        let collection: IMMDeviceCollection = enumerator.EnumAudioEndpoints(EDataFlow(1), DEVICE_STATE_ACTIVE).unwrap(); // eCapture
+        let count = collection.GetCount().unwrap_or(0);
+        let mut out = Vec::new();
+        for i in 0..count {
+            if let Ok(dev) = collection.Item(i) {
+                if let Ok(pw) = dev.GetId() {
+                    let id = pw.to_string().unwrap_or_default();
+                    if !id.is_empty() {
+                        // Friendly name fallback: use ID if we couldn't parse dshow list
+                        out.push(MicDeviceInfo { id: id.clone(), name: id });
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+// PulseAudio/PipeWire sources; ffmpeg's pulse input takes the source name as-is
+#[cfg(all(not(windows), feature = "gui"))]
+pub fn list_mic_devices() -> Vec<MicDeviceInfo> {
+    let Ok(output) = Command::new("pactl")
+        .args(["list", "short", "sources"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output() else { return Vec::new() };
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut out = Vec::new();
+    for line in text.lines() {
+        // Format: index<TAB>name<TAB>driver<TAB>spec<TAB>state
+        let Some(name) = line.split('\t').nth(1) else { continue };
+        // Monitor sources capture playback, not a microphone
+        if name.is_empty() || name.ends_with(".monitor") { continue; }
+        out.push(MicDeviceInfo { id: name.to_string(), name: name.to_string() });
+    }
+    out
+}
+
+// Device to record from when the user has not picked one in the UI
+#[cfg(windows)]
+pub fn fallback_mic_device() -> Option<MicDeviceInfo> {
+    list_mic_devices_dshow().and_then(|list| list.into_iter().next())
+}
+
+#[cfg(not(windows))]
+pub fn fallback_mic_device() -> Option<MicDeviceInfo> {
+    Some(MicDeviceInfo { id: "default".to_string(), name: "default".to_string() })
+}
+
+// Record `duration_s` seconds into latest_path, copy it to unique_path, then call on_done
+pub fn spawn_mic_recorder<F>(
+    latest_path: &Path,
+    unique_path: &Path,
+    duration_s: f64,
+    device: &str,
+    on_done: F,
+) where
+    F: FnOnce() + Send + 'static,
+{
+    let mut args: Vec<String> = Vec::new();
+    args.push("-hide_banner".to_string());
+    args.push("-loglevel".to_string());
+    args.push("error".to_string());
+    args.push("-nostdin".to_string());
+    args.push("-f".to_string());
+    args.push(MIC_INPUT_FORMAT.to_string());
+    args.push("-i".to_string());
+    args.push(device.to_string());
+    args.push("-ss".to_string());
+    args.push("0".to_string());
+    args.push("-t".to_string());
+    args.push(format!("{:.3}", duration_s.max(0.0)));
+    args.push("-ar".to_string());
+    args.push("48000".to_string());
+    args.push("-ac".to_string());
+    args.push("1".to_string());
+    args.push("-c:a".to_string());
+    args.push("pcm_s16le".to_string());
+    args.push("-y".to_string());
+    args.push(latest_path.to_string_lossy().to_string());
+
+    match Command::new("ffmpeg")
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(mut child) => {
+            let latest_path = latest_path.to_path_buf();
+            let unique_path = unique_path.to_path_buf();
+            thread::spawn(move || {
+                // Wait for process, then copy and hand over
+                match child.wait() {
+                    Ok(status) => {
+                        if !status.success() {
+                            eprintln!("ffmpeg mic exited with status {:?}", status.code());
+                        }
+                    }
+                    Err(e) => eprintln!("ffmpeg mic wait error: {}", e),
+                }
+                // Copy latest to unique (best-effort)
+                if let Err(e) = std::fs::copy(&latest_path, &unique_path) {
+                    eprintln!("copy latest_mic -> unique error: {}", e);
+                }
+                on_done();
+            });
+        }
+        Err(e) => {
+            eprintln!("ffmpeg mic spawn error: {}", e);
+        }
+    }
+}
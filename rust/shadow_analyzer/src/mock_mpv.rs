@@ -0,0 +1,315 @@
+// In-process fake mpv for tests: speaks the JSON IPC protocol on a Unix socket,
+// answers property queries from a table, logs every command it receives, and
+// lets the test push property changes and client messages.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+
+#[derive(Default)]
+struct State {
+    properties: HashMap<String, Value>,
+    // observe id -> property name, for the current connection
+    observed: HashMap<u64, String>,
+    commands: Vec<Value>,
+    // Subtitle track for sub-seek: (start, end, text), sorted by start
+    cues: Vec<(f64, f64, String)>,
+    conn: Option<UnixStream>,
+    connections: usize,
+}
+
+pub struct MockMpv {
+    path: PathBuf,
+    state: Arc<(Mutex<State>, Condvar)>,
+}
+
+impl MockMpv {
+    // Bind a fresh socket named after `tag` and accept clients (one at a time) in the background
+    pub fn start(tag: &str) -> io::Result<Self> {
+        let mut path = std::env::temp_dir();
+        path.push(format!("mpvshadow_mock_{}_{}.sock", tag, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        let state: Arc<(Mutex<State>, Condvar)> = Arc::default();
+        let st = Arc::clone(&state);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { break };
+                let Ok(writer) = stream.try_clone() else { continue };
+                {
+                    let mut g = st.0.lock().unwrap();
+                    g.observed.clear();
+                    g.conn = Some(writer);
+                    g.connections += 1;
+                }
+                st.1.notify_all();
+                serve(&st, stream);
+                if let Ok(mut g) = st.0.lock() { g.conn = None; }
+                st.1.notify_all();
+            }
+        });
+        Ok(Self { path, state })
+    }
+
+    pub fn path(&self) -> String {
+        self.path.to_string_lossy().to_string()
+    }
+
+    pub fn set_property(&self, name: &str, value: Value) {
+        self.state.0.lock().unwrap().properties.insert(name.to_string(), value);
+    }
+
+    // Typical properties for a local file with one selected audio track
+    pub fn load_media(&self, media: &Path, duration: f64, audio_ff_index: u64) {
+        self.set_property("path", json!(media.to_string_lossy()));
+        self.set_property("duration", json!(duration));
+        self.set_property("track-list", json!([
+            {"id": 1, "type": "video", "selected": true, "ff-index": 0},
+            {"id": 1, "type": "audio", "selected": true, "ff-index": audio_ff_index},
+            {"id": 1, "type": "sub", "selected": true, "ff-index": 2},
+        ]));
+    }
+
+    pub fn set_subtitles(&self, cues: &[(f64, f64, &str)]) {
+        self.state.0.lock().unwrap().cues = cues.iter().map(|(s, e, t)| (*s, *e, t.to_string())).collect();
+    }
+
+    // Send a property-change for every observer of `name` (nothing if unobserved, like mpv)
+    pub fn emit_property_change(&self, name: &str, data: Value) {
+        notify_observers(&mut self.state.0.lock().unwrap(), name, &data);
+    }
+
+    // Show a subtitle line: sub-start/sub-end first, then the sub-text change
+    pub fn emit_sub_text(&self, text: &str, start: f64, end: f64) {
+        self.set_property("sub-start", json!(start));
+        self.set_property("sub-end", json!(end));
+        self.set_property("sub-text", json!(text));
+        self.emit_property_change("sub-text", json!(text));
+    }
+
+    // Translation track line, same pattern as emit_sub_text
+    pub fn emit_secondary_sub_text(&self, text: &str, start: f64, end: f64) {
+        self.set_property("secondary-sub-start", json!(start));
+        self.set_property("secondary-sub-end", json!(end));
+        self.set_property("secondary-sub-text", json!(text));
+        self.emit_property_change("secondary-sub-text", json!(text));
+    }
+
+    // What `script-message <args...>` produces on the IPC side
+    pub fn emit_client_message(&self, args: &[&str]) {
+        let mut g = self.state.0.lock().unwrap();
+        write_line(&mut g, &json!({"event": "client-message", "args": args}));
+    }
+
+    // Wait until the given number of clients have connected so far
+    pub fn wait_connections(&self, n: usize, timeout: Duration) -> bool {
+        self.wait_until(timeout, |s| s.connections >= n && s.conn.is_some())
+    }
+
+    pub fn wait_observed(&self, name: &str, timeout: Duration) -> bool {
+        self.wait_until(timeout, |s| s.observed.values().any(|n| n == name))
+    }
+
+    // First logged command (the `command` array) matching `pred`, waiting for it if needed
+    pub fn wait_command<F: Fn(&[Value]) -> bool>(&self, timeout: Duration, pred: F) -> Option<Vec<Value>> {
+        let mut found = None;
+        self.wait_until(timeout, |s| {
+            found = s.commands.iter()
+                .filter_map(|c| c.as_array())
+                .find(|c| pred(c))
+                .cloned();
+            found.is_some()
+        });
+        found
+    }
+
+    pub fn commands(&self) -> Vec<Value> {
+        self.state.0.lock().unwrap().commands.clone()
+    }
+
+    pub fn clear_commands(&self) {
+        self.state.0.lock().unwrap().commands.clear();
+    }
+
+    // Drop the current client, as when mpv quits
+    pub fn disconnect(&self) {
+        if let Some(c) = self.state.0.lock().unwrap().conn.take() {
+            let _ = c.shutdown(std::net::Shutdown::Both);
+        }
+    }
+
+    fn wait_until<F: FnMut(&State) -> bool>(&self, timeout: Duration, mut cond: F) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut g = self.state.0.lock().unwrap();
+        loop {
+            if cond(&g) { return true; }
+            let now = Instant::now();
+            if now >= deadline { return false; }
+            g = self.state.1.wait_timeout(g, deadline - now).unwrap().0;
+        }
+    }
+}
+
+impl Drop for MockMpv {
+    fn drop(&mut self) {
+        self.disconnect();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn notify_observers(state: &mut State, name: &str, data: &Value) {
+    let ids: Vec<u64> = state.observed.iter().filter(|(_, n)| *n == name).map(|(id, _)| *id).collect();
+    for id in ids {
+        write_line(state, &json!({"event": "property-change", "id": id, "name": name, "data": data}));
+    }
+}
+
+// `sub-seek <skip>`: jump to the cue `skip` away from the one at time-pos and show it
+fn sub_seek(state: &mut State, skip: i64) -> Value {
+    let pos = state.properties.get("time-pos").and_then(|v| v.as_f64()).unwrap_or(0.0);
+    // Index of the cue at or before pos; -1 when before the first one
+    let cur = state.cues.iter().rposition(|c| c.0 <= pos).map(|i| i as i64).unwrap_or(-1);
+    let target = cur + skip;
+    if target < 0 || target >= state.cues.len() as i64 {
+        return json!({"error": "error running command"});
+    }
+    let (s, e, text) = state.cues[target as usize].clone();
+    for (k, v) in [("time-pos", json!(s)), ("sub-start", json!(s)), ("sub-end", json!(e)), ("sub-text", json!(text))] {
+        state.properties.insert(k.to_string(), v);
+    }
+    notify_observers(state, "sub-text", &json!(text));
+    json!({"error": "success"})
+}
+
+fn write_line(state: &mut State, v: &Value) {
+    if let Some(c) = state.conn.as_mut() {
+        let _ = c.write_all(format!("{}\n", v).as_bytes());
+    }
+}
+
+fn serve(st: &Arc<(Mutex<State>, Condvar)>, stream: UnixStream) {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let Ok(msg) = serde_json::from_str::<Value>(line.trim()) else { continue };
+        let cmd = msg.get("command").and_then(|c| c.as_array()).cloned().unwrap_or_default();
+        let request_id = msg.get("request_id").cloned().unwrap_or(Value::Null);
+        let mut g = st.0.lock().unwrap();
+        g.commands.push(Value::Array(cmd.clone()));
+        let mut reply = handle(&mut g, &cmd);
+        reply["request_id"] = request_id;
+        write_line(&mut g, &reply);
+        // Like mpv, a new observer gets the current value right away
+        if cmd.first().and_then(|v| v.as_str()) == Some("observe_property") {
+            if let (Some(id), Some(name)) = (cmd.get(1).and_then(|v| v.as_u64()), cmd.get(2).and_then(|v| v.as_str())) {
+                let data = g.properties.get(name).cloned().unwrap_or(Value::Null);
+                write_line(&mut g, &json!({"event": "property-change", "id": id, "name": name, "data": data}));
+            }
+        }
+        drop(g);
+        st.1.notify_all();
+    }
+}
+
+fn handle(state: &mut State, cmd: &[Value]) -> Value {
+    let name = |i: usize| cmd.get(i).and_then(|v| v.as_str()).unwrap_or("").to_string();
+    match cmd.first().and_then(|v| v.as_str()).unwrap_or("") {
+        "get_property" => match state.properties.get(&name(1)) {
+            Some(v) => json!({"data": v, "error": "success"}),
+            None => json!({"error": "property unavailable"}),
+        },
+        "set_property" => {
+            let v = cmd.get(2).cloned().unwrap_or(Value::Null);
+            state.properties.insert(name(1), v);
+            json!({"error": "success"})
+        }
+        "observe_property" => {
+            if let Some(id) = cmd.get(1).and_then(|v| v.as_u64()) {
+                state.observed.insert(id, name(2));
+            }
+            json!({"error": "success"})
+        }
+        "unobserve_property" => {
+            if let Some(id) = cmd.get(1).and_then(|v| v.as_u64()) {
+                state.observed.remove(&id);
+            }
+            json!({"error": "success"})
+        }
+        "sub-seek" => sub_seek(state, cmd.get(1).and_then(|v| v.as_i64()).unwrap_or(1)),
+        "" => json!({"error": "invalid parameter"}),
+        // request_event, show-text, seek, ...
+        _ => json!({"error": "success"}),
+    }
+}
+
+// 16-bit PCM WAV with a sine tone between silent margins, as synthetic "media"
+pub fn write_tone_wav(path: &Path, sample_rate: u32, channels: u16, tone_hz: f32, silence_s: f32, tone_s: f32) -> io::Result<()> {
+    let pad = (silence_s * sample_rate as f32) as usize;
+    let body = (tone_s * sample_rate as f32) as usize;
+    let mut samples: Vec<i16> = Vec::with_capacity((2 * pad + body) * channels as usize);
+    for i in 0..(2 * pad + body) {
+        let v = if i >= pad && i < pad + body {
+            let t = (i - pad) as f32 / sample_rate as f32;
+            ((2.0 * std::f32::consts::PI * tone_hz * t).sin() * 0.5 * i16::MAX as f32) as i16
+        } else { 0 };
+        for _ in 0..channels { samples.push(v); }
+    }
+    let data_len = (samples.len() * 2) as u32;
+    let block_align = channels * 2;
+    let mut out: Vec<u8> = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&channels.to_le_bytes());
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    out.extend_from_slice(&block_align.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for s in samples { out.extend_from_slice(&s.to_le_bytes()); }
+    std::fs::write(path, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::{MpvClient, MpvEvent};
+
+    #[test]
+    fn test_mock_answers_properties_and_pushes_events() {
+        let mock = MockMpv::start("self").unwrap();
+        mock.set_property("duration", json!(12.5));
+        let (client, events) = MpvClient::connect(&mock.path()).unwrap();
+        assert_eq!(client.get_property_f64("duration"), Some(12.5));
+        assert!(client.get_property("sub-start").is_err());
+        client.observe_property(7, "sub-text").unwrap();
+        // Initial value on observe, then our change
+        mock.emit_sub_text("hello", 1.0, 2.0);
+        let mut seen = Vec::new();
+        while seen.len() < 2 {
+            if let MpvEvent::PropertyChange { data, .. } = events.recv_timeout(Duration::from_secs(2)).unwrap() {
+                seen.push(data);
+            }
+        }
+        assert_eq!(seen, vec![Value::Null, json!("hello")]);
+        mock.emit_client_message(&["cut_current_sub"]);
+        match events.recv_timeout(Duration::from_secs(2)).unwrap() {
+            MpvEvent::ClientMessage { args } => assert_eq!(args, vec!["cut_current_sub".to_string()]),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(mock.wait_command(Duration::from_secs(1), |c| c[0] == "observe_property").is_some());
+    }
+}
@@ -1,6 +1,52 @@
-// Minimal MPM (NSDF-based) pitch estimation for short offline clips.
-// Pure Rust, no external deps. Optimized for clarity and acceptable speed
-// on small frames (30–40 ms) and modest tau ranges (80–350 Hz).
+// Frame-wise pitch estimation for clips, or incrementally for long recordings: MPM (NSDF
+// peaks), YIN and probabilistic YIN behind one trait, with a Viterbi pass over each frame's
+// candidates against octave jumps. Pure Rust; MPM's autocorrelation goes through realfft, so a
+// frame costs O(N log N) rather than O(N · lags).
+
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use realfft::num_complex::Complex;
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+
+use crate::cli;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum PitchMethod {
+    // McLeod pitch method: highest NSDF peak above nsdf_threshold
+    #[default]
+    Mpm,
+    // YIN: first dip of the cumulative mean normalised difference under yin_threshold
+    Yin,
+    // Probabilistic YIN: YIN over a prior on the threshold, giving a voicing probability
+    Pyin,
+}
+
+impl PitchMethod {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "mpm" => Some(Self::Mpm),
+            "yin" => Some(Self::Yin),
+            "pyin" => Some(Self::Pyin),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mpm => "mpm",
+            Self::Yin => "yin",
+            Self::Pyin => "pyin",
+        }
+    }
+}
+
+// `--pitch mpm|yin|pyin` (default mpm)
+pub fn parse_method(args: &[String]) -> Result<PitchMethod> {
+    match cli::cli_value(args, "--pitch") {
+        Some(v) => PitchMethod::parse(&v).with_context(|| format!("unknown --pitch '{}' (mpm|yin|pyin)", v)),
+        None => Ok(PitchMethod::Mpm),
+    }
+}
 
 #[derive(Clone, Copy, Debug)]
 pub struct F0Config {
@@ -9,7 +55,16 @@ pub struct F0Config {
     pub hop_size: usize,
     pub fmin_hz: f32,
     pub fmax_hz: f32,
+    pub method: PitchMethod,
+    // MPM: lowest NSDF peak taken as voiced
     pub nsdf_threshold: f32,
+    // YIN: dip depth (normalised difference) taken as voiced
+    pub yin_threshold: f32,
+    // pYIN: lowest voicing probability taken as voiced
+    pub voicing_threshold: f32,
+    // Decode the track from every frame's candidates (Viterbi) instead of taking each frame's
+    // own estimate
+    pub viterbi: bool,
 }
 
 impl Default for F0Config {
@@ -24,7 +79,11 @@ impl Default for F0Config {
             hop_size: hop.max(1),
             fmin_hz: 70.0,
             fmax_hz: 350.0,
+            method: PitchMethod::Mpm,
             nsdf_threshold: 0.40,
+            yin_threshold: 0.15,
+            voicing_threshold: 0.5,
+            viterbi: true,
         }
     }
 }
@@ -33,115 +92,569 @@ impl Default for F0Config {
 pub struct F0Result {
     pub f0_hz: Vec<f32>,      // 0.0 for unvoiced
     pub voiced_flags: Vec<bool>,
+    // Confidence of each frame's own estimate, also where the Viterbi track decided otherwise
+    // (0–1: MPM clarity, YIN 1 - dip depth, pYIN probability)
+    pub voicing: Vec<f32>,
+    // RMS of each analysis frame (same frames as f0_hz)
+    pub frame_rms: Vec<f32>,
     pub median_hz: Option<f32>,
     pub voiced_ratio: f32,
 }
 
-pub fn estimate_f0_mpm(samples: &[f32], cfg: &F0Config) -> F0Result {
-    if samples.is_empty() || cfg.frame_size < 3 {
-        return F0Result::default();
+impl F0Result {
+    fn push(&mut self, frame: &[f32], est: FrameF0) {
+        let sum_sq: f64 = frame.iter().map(|&v| v as f64 * v as f64).sum();
+        self.f0_hz.push(est.f0_hz.unwrap_or(0.0));
+        self.voiced_flags.push(est.f0_hz.is_some());
+        self.voicing.push(est.voicing);
+        self.frame_rms.push((sum_sq / frame.len().max(1) as f64).sqrt() as f32);
+    }
+
+    fn finish(mut self) -> Self {
+        // Compute median over voiced frames
+        let mut voiced_vals: Vec<f32> = self.f0_hz.iter().copied().filter(|&x| x > 0.0).collect();
+        voiced_vals.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        self.median_hz = if voiced_vals.is_empty() {
+            None
+        } else {
+            let mid = voiced_vals.len() / 2;
+            if voiced_vals.len() % 2 == 1 {
+                Some(voiced_vals[mid])
+            } else {
+                Some(0.5 * (voiced_vals[mid - 1] + voiced_vals[mid]))
+            }
+        };
+        self.voiced_ratio = if !self.voiced_flags.is_empty() {
+            let v = self.voiced_flags.iter().filter(|&&b| b).count() as f32;
+            v / (self.voiced_flags.len() as f32)
+        } else { 0.0 };
+        self
+    }
+}
+
+// One frame's estimate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameF0 {
+    // None when unvoiced
+    pub f0_hz: Option<f32>,
+    // Confidence that the frame is voiced, 0–1 (see F0Result::voicing)
+    pub voicing: f32,
+}
+
+// One of the F0s a frame could have, with its strength on the frame's voicing scale
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Candidate {
+    pub f0_hz: f32,
+    pub strength: f32,
+}
+
+// A pitch estimator keeps its config and scratch buffers, so one instance runs frame after
+// frame of a stream without allocating
+pub trait PitchEstimator {
+    fn config(&self) -> &F0Config;
+
+    // Estimate for one frame of config().frame_size samples
+    fn frame(&mut self, frame: &[f32]) -> FrameF0;
+
+    // Up to TOP_K candidates of the last frame, strongest first. Empty means the frame's
+    // own estimate is the only one.
+    fn candidates(&self) -> &[Candidate] { &[] }
+
+    // Whole signal: frames of frame_size every hop_size
+    fn estimate(&mut self, samples: &[f32]) -> F0Result {
+        let cfg = *self.config();
+        let mut frames = Frames::new(&cfg);
+        if cfg.frame_size >= 3 {
+            let mut at = 0;
+            while at + cfg.frame_size <= samples.len() {
+                let frame = &samples[at..at + cfg.frame_size];
+                let est = self.frame(frame);
+                frames.push(frame, est, self.candidates());
+                at += cfg.hop_size.max(1);
+            }
+        }
+        frames.finish()
+    }
+}
+
+// The estimator cfg.method names
+pub fn estimator(cfg: &F0Config) -> Box<dyn PitchEstimator> {
+    match cfg.method {
+        PitchMethod::Mpm => Box::new(Mpm::new(cfg)),
+        PitchMethod::Yin => Box::new(Yin::new(cfg)),
+        PitchMethod::Pyin => Box::new(Pyin::new(cfg)),
     }
+}
+
+pub fn estimate_f0(samples: &[f32], cfg: &F0Config) -> F0Result {
+    estimator(cfg).estimate(samples)
+}
+
+// Incremental estimation: samples arrive in blocks of any size and each frame is analysed as
+// soon as it is complete, so only one frame of audio is held. Same frames and results as one
+// call to estimate_f0 over the concatenated input.
+pub struct F0Stream {
+    estimator: Box<dyn PitchEstimator>,
+    // Pending samples; the next frame starts `skip` samples in (past the end if hop > frame)
+    buf: Vec<f32>,
+    skip: usize,
+    frames: Frames,
+}
+
+impl F0Stream {
+    pub fn new(cfg: &F0Config) -> Self {
+        Self { estimator: estimator(cfg), buf: Vec::new(), skip: 0, frames: Frames::new(cfg) }
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        let cfg = *self.estimator.config();
+        let frame_size = cfg.frame_size;
+        if frame_size < 3 { return; }
+        self.buf.extend_from_slice(samples);
+        let hop = cfg.hop_size.max(1);
+        while self.skip + frame_size <= self.buf.len() {
+            let frame = &self.buf[self.skip..self.skip + frame_size];
+            let est = self.estimator.frame(frame);
+            self.frames.push(frame, est, self.estimator.candidates());
+            self.skip += hop;
+        }
+        let done = self.skip.min(self.buf.len());
+        self.buf.drain(..done);
+        self.skip -= done;
+    }
+
+    pub fn finish(self) -> F0Result {
+        self.frames.finish()
+    }
+}
+
+// Frame results as they come in, and the Viterbi lattice when cfg.viterbi is set
+struct Frames {
+    result: F0Result,
+    viterbi: Option<Viterbi>,
+}
+
+impl Frames {
+    fn new(cfg: &F0Config) -> Self {
+        Self { result: F0Result::default(), viterbi: cfg.viterbi.then(|| Viterbi::new(cfg)) }
+    }
+
+    fn push(&mut self, frame: &[f32], est: FrameF0, candidates: &[Candidate]) {
+        self.result.push(frame, est);
+        if let Some(v) = self.viterbi.as_mut() {
+            match est.f0_hz {
+                Some(f0_hz) if candidates.is_empty() => v.push(&[Candidate { f0_hz, strength: est.voicing }]),
+                _ => v.push(candidates),
+            }
+        }
+    }
+
+    fn finish(mut self) -> F0Result {
+        if let Some(v) = self.viterbi {
+            let track = v.decode();
+            self.result.voiced_flags = track.iter().map(|&f| f > 0.0).collect();
+            self.result.f0_hz = track;
+        }
+        self.result.finish()
+    }
+}
+
+// Candidates kept per frame
+const TOP_K: usize = 5;
+// Path cost of a pitch change between frames, per semitone: an octave (12) costs 0.6, more
+// than the difference between a clear peak and a threshold one. Speech glides move well under
+// a semitone per 10 ms hop.
+const SEMITONE_COST: f32 = 0.05;
+// Path cost of a change between voiced and unvoiced
+const VOICING_COST: f32 = 0.3;
+// MPM candidate strength taken off per octave of lag (as Praat's octave cost)
+const OCTAVE_COST: f32 = 0.01;
+
+// Viterbi decoding over every frame's candidates plus an unvoiced state. A candidate costs
+// 1 - strength and the unvoiced state 1 - the estimator's voicing threshold, so a frame on its
+// own is decided the way the estimator decides it; the transitions make a frame whose
+// strongest peak is an octave or a sub-harmonic off its neighbours follow them instead.
+struct Viterbi {
+    unvoiced_cost: f32,
+    // Every frame's states in order, the unvoiced state last: F0 (0.0 unvoiced) and the best
+    // predecessor among the previous frame's states
+    states: Vec<(f32, u8)>,
+    // Where each frame's states start in `states`
+    starts: Vec<usize>,
+    // Cheapest path cost to each state of the last frame, and of the frame being added
+    cost: Vec<f32>,
+    next: Vec<f32>,
+}
+
+impl Viterbi {
+    fn new(cfg: &F0Config) -> Self {
+        // The estimators' thresholds on the strength scale of their candidates
+        let voiced_at = match cfg.method {
+            PitchMethod::Mpm => cfg.nsdf_threshold,
+            PitchMethod::Yin => 1.0 - cfg.yin_threshold,
+            PitchMethod::Pyin => cfg.voicing_threshold,
+        };
+        Self {
+            unvoiced_cost: 1.0 - voiced_at.clamp(0.0, 1.0),
+            states: Vec::new(),
+            starts: Vec::new(),
+            cost: Vec::new(),
+            next: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, candidates: &[Candidate]) {
+        let prev = self.starts.last().copied();
+        self.starts.push(self.states.len());
+        self.next.clear();
+        let emissions = candidates.iter().take(TOP_K)
+            .map(|c| (c.f0_hz, 1.0 - c.strength.clamp(0.0, 1.0)))
+            .chain(std::iter::once((0.0, self.unvoiced_cost)));
+        for (f0, emission) in emissions {
+            let (mut from, mut best) = (0usize, 0.0f32);
+            if let Some(p0) = prev {
+                best = f32::INFINITY;
+                for (i, &c) in self.cost.iter().enumerate() {
+                    let total = c + transition(self.states[p0 + i].0, f0);
+                    if total < best { (from, best) = (i, total); }
+                }
+            }
+            self.states.push((f0, from as u8));
+            self.next.push(best + emission);
+        }
+        std::mem::swap(&mut self.cost, &mut self.next);
+    }
+
+    // F0 per frame along the cheapest path, 0.0 where it is unvoiced
+    fn decode(self) -> Vec<f32> {
+        let mut track = vec![0.0f32; self.starts.len()];
+        let Some(mut state) = self.cost.iter().enumerate().min_by(|a, b| a.1.total_cmp(b.1)).map(|(i, _)| i) else {
+            return track;
+        };
+        for (f, &start) in self.starts.iter().enumerate().rev() {
+            let (f0, from) = self.states[start + state];
+            track[f] = f0;
+            state = from as usize;
+        }
+        track
+    }
+}
+
+fn transition(from_hz: f32, to_hz: f32) -> f32 {
+    match (from_hz > 0.0, to_hz > 0.0) {
+        (true, true) => SEMITONE_COST * (12.0 * (to_hz / from_hz).log2()).abs(),
+        (false, false) => 0.0,
+        _ => VOICING_COST,
+    }
+}
+
+// Lags for [fmin, fmax]: the shortest is at least 2 samples
+pub fn lag_range(cfg: &F0Config) -> (usize, usize) {
     let sr = cfg.sample_rate_hz.max(1.0);
     let tau_min = ((sr / cfg.fmax_hz.max(1.0)).floor() as usize).max(2);
     let tau_max = ((sr / cfg.fmin_hz.max(1.0)).ceil() as usize).max(tau_min + 1);
-    let nsdf_thresh = cfg.nsdf_threshold.clamp(0.0, 1.0);
+    (tau_min, tau_max)
+}
 
-    let frame_size = cfg.frame_size;
-    let hop = cfg.hop_size.max(1);
-    let mut nsdf: Vec<f32> = vec![0.0; tau_max + 1];
+// Frequency for a lag, refined by a parabola through the neighbouring values of `curve`
+fn refined_freq(curve: &[f32], tau: usize, cfg: &F0Config, tau_min: usize, tau_max: usize) -> Option<f32> {
+    let delta = if tau > 0 && tau + 1 < curve.len() {
+        let (l, c, r) = (curve[tau - 1], curve[tau], curve[tau + 1]);
+        let denom = l - 2.0 * c + r;
+        if denom.abs() > 1e-12 { 0.5 * (l - r) / denom } else { 0.0 }
+    } else { 0.0 };
+    let tau_refined = (tau as f32 + delta).max(tau_min as f32).min(tau_max as f32);
+    let freq = cfg.sample_rate_hz.max(1.0) / tau_refined.max(1.0);
+    (freq.is_finite() && freq > 0.0).then_some(freq)
+}
+
+// Autocorrelation of a frame through a zero-padded real FFT (Wiener–Khinchin). Padding to
+// more than frame + max_lag keeps the lags up to max_lag free of wrap-around; the plans and
+// buffers are made once and reused for every frame.
+struct Autocorrelation {
+    frame_size: usize,
+    fwd: Arc<dyn RealToComplex<f64>>,
+    inv: Arc<dyn ComplexToReal<f64>>,
+    time: Vec<f64>,
+    spectrum: Vec<Complex<f64>>,
+    scratch: Vec<Complex<f64>>,
+}
 
-    let mut f0_series: Vec<f32> = Vec::new();
-    let mut voiced_flags: Vec<bool> = Vec::new();
+impl Autocorrelation {
+    fn new(frame_size: usize, max_lag: usize) -> Self {
+        let len = (frame_size + max_lag + 1).next_power_of_two();
+        let mut planner = RealFftPlanner::<f64>::new();
+        let (fwd, inv) = (planner.plan_fft_forward(len), planner.plan_fft_inverse(len));
+        let scratch = vec![Complex::default(); fwd.get_scratch_len().max(inv.get_scratch_len())];
+        Self { frame_size, time: fwd.make_input_vec(), spectrum: fwd.make_output_vec(), scratch, fwd, inv }
+    }
 
-    let mut start = 0usize;
-    while start + frame_size <= samples.len() {
-        let frame = &samples[start..start + frame_size];
+    // r(tau) = sum_j x_j x_{j+tau}, valid for tau up to max_lag; None if a transform fails
+    fn compute(&mut self, frame: &[f32]) -> Option<&[f64]> {
+        let scale = 1.0 / self.time.len() as f64;
+        for (t, &x) in self.time.iter_mut().zip(frame) { *t = x as f64; }
+        self.time[frame.len()..].fill(0.0);
+        self.fwd.process_with_scratch(&mut self.time, &mut self.spectrum, &mut self.scratch).ok()?;
+        for c in self.spectrum.iter_mut() {
+            *c = Complex::new(c.norm_sqr() * scale, 0.0);
+        }
+        self.inv.process_with_scratch(&mut self.spectrum, &mut self.time, &mut self.scratch).ok()?;
+        Some(&self.time)
+    }
+}
+
+pub struct Mpm {
+    cfg: F0Config,
+    tau_min: usize,
+    tau_max: usize,
+    nsdf: Vec<f32>,
+    acf: Autocorrelation,
+    // NSDF peaks of the last frame as (lag, value), then as candidates
+    peaks: Vec<(usize, f32)>,
+    candidates: Vec<Candidate>,
+}
+
+impl Mpm {
+    pub fn new(cfg: &F0Config) -> Self {
+        let (tau_min, tau_max) = lag_range(cfg);
+        let acf = Autocorrelation::new(cfg.frame_size, tau_max);
+        Self { cfg: *cfg, tau_min, tau_max, nsdf: vec![0.0; tau_max + 1], acf, peaks: Vec::new(), candidates: Vec::new() }
+    }
+
+    // NSDF of a frame for lags up to tau_max (zero below tau_min); None if a transform fails
+    pub fn compute_nsdf(&mut self, frame: &[f32]) -> Option<&[f32]> {
+        let (tau_min, tau_max, nsdf) = (self.tau_min, self.tau_max, &mut self.nsdf);
+        let frame_size = frame.len();
+        if self.acf.frame_size != frame_size {
+            self.acf = Autocorrelation::new(frame_size, tau_max);
+        }
+        let r = self.acf.compute(frame)?;
 
         // Compute NSDF(tau) for tau in [tau_min, tau_max]
         // NSDF(tau) = 2 * sum_j x_j x_{j+tau} / (sum_j x_j^2 + x_{j+tau}^2)
-        // where j runs so that indices are valid within frame.
-        for tau in tau_min..=tau_max {
-            let limit = frame_size - tau;
-            if limit < 2 {
+        // where j runs so that indices are valid within frame. The denominator m(tau) starts
+        // at 2 r(0) and each lag drops one sample off either end of the overlap.
+        let sq = |i: usize| frame[i] as f64 * frame[i] as f64;
+        let m0 = 2.0 * r[0];
+        let mut m = m0;
+        for tau in 1..=tau_max {
+            if tau + 2 > frame_size {
                 nsdf[tau] = 0.0;
                 continue;
             }
-            let mut num: f64 = 0.0;
-            let mut den: f64 = 0.0;
-            // Naive loops; frame sizes are small (<= ~1024).
-            for j in 0..limit {
-                let a = frame[j] as f64;
-                let b = frame[j + tau] as f64;
-                num += a * b;
-                den += a * a + b * b;
+            m -= sq(tau - 1) + sq(frame_size - tau);
+            if tau >= tau_min {
+                // What is left of a (nearly) silent overlap is rounding error
+                nsdf[tau] = if m > m0 * 1e-12 { (2.0 * r[tau] / m) as f32 } else { 0.0 };
             }
-            let v = if den > 0.0 { (2.0 * num / den) as f32 } else { 0.0 };
-            nsdf[tau] = v;
         }
+        Some(nsdf)
+    }
+}
+
+// The direct O(N · lags) NSDF that the FFT path replaced: 2 * sum_j x_j x_{j+tau} /
+// sum_j (x_j^2 + x_{j+tau}^2) for every lag. Reference for the test and the bench only.
+#[doc(hidden)]
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn nsdf_direct(frame: &[f32], tau_min: usize, tau_max: usize, nsdf: &mut [f32]) {
+    for tau in tau_min..=tau_max {
+        if tau + 2 > frame.len() { continue; }
+        let (mut num, mut den) = (0.0f64, 0.0f64);
+        for j in 0..frame.len() - tau {
+            let (a, b) = (frame[j] as f64, frame[j + tau] as f64);
+            num += a * b;
+            den += a * a + b * b;
+        }
+        nsdf[tau] = if den > 0.0 { (2.0 * num / den) as f32 } else { 0.0 };
+    }
+}
+
+impl PitchEstimator for Mpm {
+    fn config(&self) -> &F0Config { &self.cfg }
+
+    fn candidates(&self) -> &[Candidate] { &self.candidates }
+
+    fn frame(&mut self, frame: &[f32]) -> FrameF0 {
+        self.candidates.clear();
+        if self.compute_nsdf(frame).is_none() {
+            return FrameF0 { f0_hz: None, voicing: 0.0 };
+        }
+        let (tau_min, tau_max, nsdf) = (self.tau_min, self.tau_max, &self.nsdf);
+        let nsdf_thresh = self.cfg.nsdf_threshold.clamp(0.0, 1.0);
 
         // Peak picking: choose highest local max between tau_min..tau_max.
         // Optional refinement: parabolic interpolation around the best peak.
+        // The positive peaks are the candidates, less OCTAVE_COST per octave below fmax:
+        // a periodic frame peaks about as high at every multiple of its period.
         let mut best_tau = 0usize;
         let mut best_val = -1.0f32;
+        self.peaks.clear();
         for tau in (tau_min + 1)..tau_max {
             let prev = nsdf[tau - 1];
             let cur = nsdf[tau];
             let next = nsdf[tau + 1];
-            if cur > prev && cur >= next && cur > best_val {
-                best_val = cur;
-                best_tau = tau;
+            if cur > prev && cur >= next {
+                if cur > best_val {
+                    best_val = cur;
+                    best_tau = tau;
+                }
+                let strength = cur - OCTAVE_COST * (tau as f32 / tau_min as f32).log2();
+                if strength > 0.0 { self.peaks.push((tau, strength)); }
             }
         }
-
-        let mut f0_hz = 0.0f32;
-        let mut voiced = false;
-        if best_tau >= tau_min && best_tau <= tau_max && best_val >= nsdf_thresh {
-            // Parabolic interpolation for sub-sample tau (optional)
-            let l = nsdf[best_tau - 1];
-            let c = nsdf[best_tau];
-            let r = nsdf[best_tau + 1];
-            let denom = (l - 2.0 * c + r);
-            let delta = if denom.abs() > 1e-12 { 0.5 * (l - r) / denom } else { 0.0 };
-            let tau_refined = (best_tau as f32 + delta).max(tau_min as f32).min(tau_max as f32);
-            let freq = sr / tau_refined.max(1.0);
-            if freq.is_finite() && freq > 0.0 {
-                f0_hz = freq;
-                voiced = true;
+        self.peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+        for &(tau, strength) in self.peaks.iter().take(TOP_K) {
+            if let Some(f0_hz) = refined_freq(nsdf, tau, &self.cfg, tau_min, tau_max) {
+                self.candidates.push(Candidate { f0_hz, strength: strength.min(1.0) });
             }
         }
 
-        f0_series.push(if voiced { f0_hz } else { 0.0 });
-        voiced_flags.push(voiced);
+        let voicing = best_val.clamp(0.0, 1.0);
+        let f0_hz = if best_tau >= tau_min && best_tau <= tau_max && best_val >= nsdf_thresh {
+            refined_freq(nsdf, best_tau, &self.cfg, tau_min, tau_max)
+        } else { None };
+        FrameF0 { f0_hz, voicing }
+    }
+}
 
-        start += hop;
+// Cumulative mean normalised difference d'(tau) for tau in 1..=tau_max (d'(0) = 1) over an
+// integration window of frame - tau_max samples. False when the frame is too short.
+fn cmndf(frame: &[f32], tau_max: usize, d: &mut [f32]) -> bool {
+    let window = frame.len().saturating_sub(tau_max);
+    if window < 2 { return false; }
+    d[0] = 1.0;
+    let mut running: f64 = 0.0;
+    for tau in 1..=tau_max {
+        let mut diff: f64 = 0.0;
+        for j in 0..window {
+            let delta = frame[j] as f64 - frame[j + tau] as f64;
+            diff += delta * delta;
+        }
+        running += diff;
+        d[tau] = if running > 0.0 { (diff * tau as f64 / running) as f32 } else { 1.0 };
     }
+    true
+}
 
-    // Compute median over voiced frames
-    let mut voiced_vals: Vec<f32> = f0_series.iter().copied().filter(|&x| x > 0.0).collect();
-    voiced_vals.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-    let median = if voiced_vals.is_empty() {
-        None
-    } else {
-        let mid = voiced_vals.len() / 2;
-        if voiced_vals.len() % 2 == 1 {
-            Some(voiced_vals[mid])
-        } else {
-            Some(0.5 * (voiced_vals[mid - 1] + voiced_vals[mid]))
+pub struct Yin {
+    cfg: F0Config,
+    tau_min: usize,
+    tau_max: usize,
+    cmndf: Vec<f32>,
+}
+
+impl Yin {
+    pub fn new(cfg: &F0Config) -> Self {
+        let (tau_min, tau_max) = lag_range(cfg);
+        Self { cfg: *cfg, tau_min, tau_max, cmndf: vec![1.0; tau_max + 1] }
+    }
+}
+
+impl PitchEstimator for Yin {
+    fn config(&self) -> &F0Config { &self.cfg }
+
+    fn frame(&mut self, frame: &[f32]) -> FrameF0 {
+        let (tau_min, tau_max, d) = (self.tau_min, self.tau_max, &mut self.cmndf);
+        if !cmndf(frame, tau_max, d) {
+            return FrameF0 { f0_hz: None, voicing: 0.0 };
         }
-    };
+        // First dip under the threshold, followed down to its bottom; the shortest lag that
+        // qualifies is the period rather than a multiple of it
+        let threshold = self.cfg.yin_threshold.clamp(0.0, 1.0);
+        let mut tau = tau_min;
+        while tau <= tau_max && d[tau] >= threshold { tau += 1; }
+        if tau > tau_max {
+            let deepest = d[tau_min..=tau_max].iter().copied().fold(1.0f32, f32::min);
+            return FrameF0 { f0_hz: None, voicing: (1.0 - deepest).clamp(0.0, 1.0) };
+        }
+        while tau < tau_max && d[tau + 1] < d[tau] { tau += 1; }
+        FrameF0 { f0_hz: refined_freq(d, tau, &self.cfg, tau_min, tau_max), voicing: (1.0 - d[tau]).clamp(0.0, 1.0) }
+    }
+}
 
-    let voiced_ratio = if !voiced_flags.is_empty() {
-        let v = voiced_flags.iter().filter(|&&b| b).count() as f32;
-        v / (voiced_flags.len() as f32)
-    } else { 0.0 };
+// pYIN thresholds 0.01, 0.02, ..., 1.00, with a Beta(2, 18) prior (mean 0.1)
+const PYIN_THRESHOLDS: usize = 100;
+const PYIN_BETA: (i32, i32) = (2, 18);
 
-    F0Result { f0_hz: f0_series, voiced_flags, median_hz: median, voiced_ratio }
+// Mauch & Dixon's first stage: each threshold of the prior picks the first dip of d' under
+// it, so every dip collects the prior mass of the thresholds that chose it. Their sum is the
+// probability the frame is voiced; the dip with the most mass gives the F0. Frames are
+// decided on their own here; the dips are the candidates the Viterbi pass decodes over frames
+// (the second stage), on the voicing scale: the dip with the most mass has the probability.
+pub struct Pyin {
+    cfg: F0Config,
+    tau_min: usize,
+    tau_max: usize,
+    cmndf: Vec<f32>,
+    prior: Vec<f32>,
+    // Local minima of d' and the mass each collects, reused
+    dips: Vec<(usize, f32)>,
+    candidates: Vec<Candidate>,
+}
+
+impl Pyin {
+    pub fn new(cfg: &F0Config) -> Self {
+        let (tau_min, tau_max) = lag_range(cfg);
+        let (a, b) = PYIN_BETA;
+        let density: Vec<f64> = (1..=PYIN_THRESHOLDS)
+            .map(|i| {
+                let s = i as f64 / PYIN_THRESHOLDS as f64;
+                s.powi(a - 1) * (1.0 - s).powi(b - 1)
+            })
+            .collect();
+        let total: f64 = density.iter().sum();
+        let prior = density.iter().map(|p| (p / total) as f32).collect();
+        Self { cfg: *cfg, tau_min, tau_max, cmndf: vec![1.0; tau_max + 1], prior, dips: Vec::new(), candidates: Vec::new() }
+    }
+}
+
+impl PitchEstimator for Pyin {
+    fn config(&self) -> &F0Config { &self.cfg }
+
+    fn candidates(&self) -> &[Candidate] { &self.candidates }
+
+    fn frame(&mut self, frame: &[f32]) -> FrameF0 {
+        let (tau_min, tau_max, d) = (self.tau_min, self.tau_max, &mut self.cmndf);
+        self.candidates.clear();
+        if !cmndf(frame, tau_max, d) {
+            return FrameF0 { f0_hz: None, voicing: 0.0 };
+        }
+        self.dips.clear();
+        for tau in tau_min..=tau_max {
+            if d[tau] < d[tau - 1] && (tau == tau_max || d[tau] <= d[tau + 1]) {
+                self.dips.push((tau, 0.0));
+            }
+        }
+        for (i, &p) in self.prior.iter().enumerate() {
+            let s = (i + 1) as f32 / PYIN_THRESHOLDS as f32;
+            if let Some(dip) = self.dips.iter_mut().find(|(tau, _)| d[*tau] < s) {
+                dip.1 += p;
+            }
+        }
+        let voicing = self.dips.iter().map(|(_, p)| p).sum::<f32>().clamp(0.0, 1.0);
+        let best = self.dips.iter().copied().filter(|(_, p)| *p > 0.0).max_by(|a, b| a.1.total_cmp(&b.1));
+        let f0_hz = match best {
+            Some((tau, _)) if voicing >= self.cfg.voicing_threshold => refined_freq(d, tau, &self.cfg, tau_min, tau_max),
+            _ => None,
+        };
+        if let Some((_, most)) = best {
+            self.dips.sort_by(|a, b| b.1.total_cmp(&a.1));
+            for &(tau, p) in self.dips.iter().filter(|(_, p)| *p > 0.0).take(TOP_K) {
+                if let Some(f0_hz) = refined_freq(d, tau, &self.cfg, tau_min, tau_max) {
+                    self.candidates.push(Candidate { f0_hz, strength: voicing * p / most });
+                }
+            }
+        }
+        FrameF0 { f0_hz, voicing }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const METHODS: [PitchMethod; 3] = [PitchMethod::Mpm, PitchMethod::Yin, PitchMethod::Pyin];
+
     fn gen_sine(sr: f32, freq: f32, secs: f32) -> Vec<f32> {
         let n = (sr * secs) as usize;
         let mut out = Vec::with_capacity(n);
@@ -158,23 +671,167 @@ mod tests {
     fn test_sine_200hz_ok() {
         let sr = 24000.0;
         let sig = gen_sine(sr, 200.0, 0.5);
-        let mut cfg = F0Config::default();
-        cfg.sample_rate_hz = sr;
-        let res = estimate_f0_mpm(&sig, &cfg);
-        assert!(res.voiced_ratio > 0.7, "voiced_ratio={}", res.voiced_ratio);
-        let med = res.median_hz.expect("median");
-        assert!((med - 200.0).abs() < 3.0, "median={}", med);
+        for method in METHODS {
+            let cfg = F0Config { sample_rate_hz: sr, method, ..Default::default() };
+            let res = estimate_f0(&sig, &cfg);
+            assert!(res.voiced_ratio > 0.7, "{:?} voiced_ratio={}", method, res.voiced_ratio);
+            assert_eq!(res.voiced_flags.len(), res.f0_hz.len());
+            assert_eq!(res.voicing.len(), res.f0_hz.len());
+            let med = res.median_hz.expect("median");
+            assert!((med - 200.0).abs() < 3.0, "{:?} median={}", method, med);
+        }
     }
 
     #[test]
     fn test_silence_unvoiced() {
         let sr = 24000.0;
         let sig = vec![0.0f32; (sr as usize) / 2];
+        for method in METHODS {
+            let cfg = F0Config { method, ..Default::default() };
+            let res = estimate_f0(&sig, &cfg);
+            assert!(res.median_hz.is_none(), "{:?}", method);
+            assert!(res.voiced_ratio < 0.05, "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn test_yin_takes_the_period_not_a_multiple() {
+        // Fundamental much weaker than its second harmonic: the NSDF's highest peak and YIN's
+        // first dip must both still be at 150 Hz, not 300 Hz or 75 Hz
+        let sr = 24000.0;
+        let n = (sr * 0.4) as usize;
+        let sig: Vec<f32> = (0..n).map(|i| {
+            let t = i as f32 / sr;
+            0.15 * (std::f32::consts::TAU * 150.0 * t).sin() + 0.5 * (std::f32::consts::TAU * 300.0 * t).sin()
+        }).collect();
+        for method in METHODS {
+            let res = estimate_f0(&sig, &F0Config { sample_rate_hz: sr, method, ..Default::default() });
+            let med = res.median_hz.expect("median");
+            assert!((med - 150.0).abs() < 3.0, "{:?} median={}", method, med);
+        }
+    }
+
+    #[test]
+    fn test_pyin_voicing_probability() {
+        let sr = 24000.0;
+        let cfg = F0Config { sample_rate_hz: sr, method: PitchMethod::Pyin, ..Default::default() };
+        let tone = estimate_f0(&gen_sine(sr, 180.0, 0.3), &cfg);
+        let mean = tone.voicing.iter().sum::<f32>() / tone.voicing.len() as f32;
+        assert!(mean > 0.9, "tone voicing {}", mean);
+        // White noise has no dip deep enough for most of the prior
+        let res = estimate_f0(&crate::test_signals::noise(1, 7200, 0.5), &cfg);
+        assert!(res.voiced_ratio < 0.1, "noise voiced {}", res.voiced_ratio);
+        assert!(res.voicing.iter().all(|&p| p < 0.5), "{:?}", res.voicing);
+    }
+
+    // Frames that exercise the NSDF: tones, harmonics over noise, an onset at the frame end
+    fn nsdf_frames(cfg: &F0Config) -> Vec<Vec<f32>> {
+        let n = cfg.frame_size;
+        let mut x = 7u32;
+        let mut noise = move || {
+            x = x.wrapping_mul(1664525).wrapping_add(1013904223);
+            (x >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+        };
+        let sine = |hz: f32, i: usize| (std::f32::consts::TAU * hz * i as f32 / cfg.sample_rate_hz).sin();
+        vec![
+            (0..n).map(|i| 0.5 * sine(200.0, i)).collect(),
+            (0..n).map(|i| 0.3 * sine(97.0, i) + 0.2 * sine(194.0, i) + 0.05 * noise()).collect(),
+            (0..n).map(|_| noise()).collect(),
+            (0..n).map(|i| if i + 40 > n { 0.4 * sine(150.0, i) } else { 0.0 }).collect(),
+            vec![0.0; n],
+        ]
+    }
+
+    #[test]
+    fn test_fft_nsdf_matches_direct_sum() {
         let cfg = F0Config::default();
-        let res = estimate_f0_mpm(&sig, &cfg);
-        assert!(res.median_hz.is_none());
-        assert!(res.voiced_ratio < 0.05);
+        let (tau_min, tau_max) = lag_range(&cfg);
+        let mut mpm = Mpm::new(&cfg);
+        for (k, frame) in nsdf_frames(&cfg).iter().enumerate() {
+            let nsdf = mpm.compute_nsdf(frame).expect("nsdf");
+            let mut reference = vec![0.0f32; tau_max + 1];
+            nsdf_direct(frame, tau_min, tau_max, &mut reference);
+            let worst = (tau_min..=tau_max).map(|t| (nsdf[t] - reference[t]).abs()).fold(0.0f32, f32::max);
+            assert!(worst < 1e-5, "frame {}: max NSDF difference {}", k, worst);
+        }
     }
-}
 
+    #[test]
+    fn test_parse_method() {
+        let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(parse_method(&args(&[])).unwrap(), PitchMethod::Mpm);
+        assert_eq!(parse_method(&args(&["--pitch", "pYIN"])).unwrap(), PitchMethod::Pyin);
+        assert!(parse_method(&args(&["--pitch=crepe"])).is_err());
+    }
 
+    #[test]
+    fn test_stream_matches_one_shot() {
+        let sr = 24000.0;
+        let mut sig = gen_sine(sr, 150.0, 0.4);
+        sig.extend(gen_sine(sr, 250.0, 0.4));
+        for method in METHODS {
+            let cfg = F0Config { sample_rate_hz: sr, method, ..Default::default() };
+            let whole = estimate_f0(&sig, &cfg);
+            // Odd block sizes, smaller and larger than a frame
+            let mut stream = F0Stream::new(&cfg);
+            for block in sig.chunks(317) {
+                stream.push(block);
+            }
+            let streamed = stream.finish();
+            assert_eq!(streamed.f0_hz, whole.f0_hz);
+            assert_eq!(streamed.voicing, whole.voicing);
+            assert_eq!(streamed.frame_rms, whole.frame_rms);
+            assert_eq!(streamed.median_hz, whole.median_hz);
+            // Hop longer than the frame: the gaps between frames are skipped, not analysed
+            let sparse = F0Config { hop_size: 1500, ..cfg };
+            let mut stream = F0Stream::new(&sparse);
+            for block in sig.chunks(500) { stream.push(block); }
+            assert_eq!(stream.finish().f0_hz, estimate_f0(&sig, &sparse).f0_hz);
+        }
+    }
+
+    #[test]
+    fn test_viterbi_keeps_the_contour_through_octave_flips() {
+        let c = |f0_hz, strength| Candidate { f0_hz, strength };
+        let cfg = F0Config::default();
+        let mut v = Viterbi::new(&cfg);
+        for i in 0..30 {
+            match i {
+                // The sub-harmonic is the strongest peak for a few frames
+                8..=10 => v.push(&[c(100.0, 0.9), c(200.0 + i as f32, 0.85)]),
+                0..=19 => v.push(&[c(200.0 + i as f32, 0.9), c(100.0, 0.8)]),
+                // A weak frame, then real silence
+                20 => v.push(&[c(220.0, 0.45)]),
+                _ => v.push(&[]),
+            }
+        }
+        let track = v.decode();
+        assert_eq!(track.len(), 30);
+        assert_eq!(track[9], 209.0);
+        assert!(track[..20].iter().enumerate().all(|(i, &f)| (f - 200.0 - i as f32).abs() <= 1.0), "{:?}", track);
+        assert!(track[21..].iter().all(|&f| f == 0.0), "{:?}", track);
+        // Nothing pushed
+        assert!(Viterbi::new(&cfg).decode().is_empty());
+    }
+
+    #[test]
+    fn test_viterbi_removes_a_brief_subharmonic() {
+        // 200 Hz with a weak 100 Hz component for 100 ms: those frames repeat at 100 Hz, so the
+        // highest NSDF peak is an octave down
+        let sr = 24000.0;
+        let n = (sr * 0.6) as usize;
+        let sig: Vec<f32> = (0..n).map(|i| {
+            let t = i as f32 / sr;
+            let sub = if (0.25..0.35).contains(&t) { 0.08 } else { 0.0 };
+            0.5 * (std::f32::consts::TAU * 200.0 * t).sin() + sub * (std::f32::consts::TAU * 100.0 * t).sin()
+        }).collect();
+        let per_frame = F0Config { sample_rate_hz: sr, viterbi: false, ..Default::default() };
+        let flips = estimate_f0(&sig, &per_frame).f0_hz.iter().filter(|&&f| (f - 100.0).abs() < 5.0).count();
+        assert!(flips >= 3, "{} frames at 100 Hz without the Viterbi pass", flips);
+        for method in METHODS {
+            let cfg = F0Config { sample_rate_hz: sr, method, ..Default::default() };
+            let res = estimate_f0(&sig, &cfg);
+            assert!(res.f0_hz.iter().all(|&f| (f - 200.0).abs() < 5.0), "{:?} {:?}", method, res.f0_hz);
+        }
+    }
+}
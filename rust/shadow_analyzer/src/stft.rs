@@ -0,0 +1,74 @@
+// Short-time Fourier transform for the spectral stages (enhancement, dialogue extraction):
+// 50% overlap with sqrt-Hann analysis and synthesis windows, so spectra that are left alone
+// resynthesize to the input.
+
+use std::sync::Arc;
+use anyhow::Result;
+use realfft::num_complex::Complex32;
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+
+pub struct Stft {
+    n: usize,
+    hop: usize,
+    window: Vec<f32>,
+    fwd: Arc<dyn RealToComplex<f32>>,
+    inv: Arc<dyn ComplexToReal<f32>>,
+}
+
+impl Stft {
+    // Frames of about `frame_s` seconds (even length, at least 16 samples)
+    pub fn new(frame_s: f64, sample_rate: u32) -> Self {
+        let n = (((frame_s * sample_rate as f64) / 2.0).round() as usize * 2).max(16);
+        let mut planner = RealFftPlanner::<f32>::new();
+        let window = (0..n)
+            .map(|i| (0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / n as f32).cos()).sqrt())
+            .collect();
+        Self { n, hop: n / 2, window, fwd: planner.plan_fft_forward(n), inv: planner.plan_fft_inverse(n) }
+    }
+
+    // Bins per spectrum, DC to Nyquist
+    pub fn bins(&self) -> usize {
+        self.n / 2 + 1
+    }
+
+    // Spectrum of every frame. One hop of silence goes in front so every input sample is
+    // under two frames.
+    pub fn analyze(&self, input: &[f32]) -> Result<Vec<Vec<Complex32>>> {
+        if input.is_empty() { return Ok(Vec::new()); }
+        let frames = input.len().div_ceil(self.hop) + 1;
+        let mut padded = vec![0.0f32; self.hop];
+        padded.extend_from_slice(input);
+        padded.resize((frames + 1) * self.hop, 0.0);
+        let mut buf = self.fwd.make_input_vec();
+        let mut spectra = Vec::with_capacity(frames);
+        for f in 0..frames {
+            let at = f * self.hop;
+            for (b, (x, w)) in buf.iter_mut().zip(padded[at..at + self.n].iter().zip(&self.window)) {
+                *b = x * w;
+            }
+            let mut spec = self.fwd.make_output_vec();
+            self.fwd.process(&mut buf, &mut spec).map_err(|e| anyhow::anyhow!("fft: {}", e))?;
+            spectra.push(spec);
+        }
+        Ok(spectra)
+    }
+
+    // Overlap-add spectra from `analyze` back into `len` samples
+    pub fn synthesize(&self, spectra: Vec<Vec<Complex32>>, len: usize) -> Result<Vec<f32>> {
+        let mut out = vec![0.0f32; (spectra.len() + 1) * self.hop];
+        let mut time = self.inv.make_output_vec();
+        let bins = self.bins();
+        for (f, mut spec) in spectra.into_iter().enumerate() {
+            // DC and Nyquist must stay real for the inverse transform
+            spec[0].im = 0.0;
+            spec[bins - 1].im = 0.0;
+            self.inv.process(&mut spec, &mut time).map_err(|e| anyhow::anyhow!("ifft: {}", e))?;
+            let at = f * self.hop;
+            for (o, (t, w)) in out[at..at + self.n].iter_mut().zip(time.iter().zip(&self.window)) {
+                *o += t * w / self.n as f32;
+            }
+        }
+        out.resize(self.hop + len, 0.0);
+        Ok(out[self.hop..].to_vec())
+    }
+}
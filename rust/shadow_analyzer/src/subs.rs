@@ -0,0 +1,241 @@
+// External subtitle parsing (SRT, WebVTT, ASS/SSA) into plain timed cues.
+// Styling is dropped; only the dialogue text and its window are kept.
+
+use std::path::Path;
+use anyhow::{Result, Context};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubCue {
+    // 1-based position in file order, after sorting by start time
+    pub index: usize,
+    pub start_s: f64,
+    pub end_s: f64,
+    pub text: String,
+}
+
+pub fn parse_file(path: &Path) -> Result<Vec<SubCue>> {
+    let bytes = std::fs::read(path).with_context(|| format!("read subtitles: {}", path.display()))?;
+    let text = String::from_utf8_lossy(&bytes);
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
+    let cues = match ext.as_str() {
+        "srt" => parse_srt(&text),
+        "vtt" => parse_vtt(&text),
+        "ass" | "ssa" => parse_ass(&text),
+        _ => parse_any(&text),
+    };
+    if cues.is_empty() {
+        anyhow::bail!("no subtitle cues found in {}", path.display());
+    }
+    Ok(cues)
+}
+
+// Guess the format from the content when the extension does not tell
+pub fn parse_any(text: &str) -> Vec<SubCue> {
+    let t = strip_bom(text).trim_start();
+    if t.starts_with("WEBVTT") {
+        parse_vtt(t)
+    } else if t.starts_with("[Script Info]") || t.contains("\n[Events]") {
+        parse_ass(t)
+    } else {
+        parse_srt(t)
+    }
+}
+
+pub fn parse_srt(text: &str) -> Vec<SubCue> {
+    parse_arrow_blocks(strip_bom(text))
+}
+
+pub fn parse_vtt(text: &str) -> Vec<SubCue> {
+    let body = strip_bom(text);
+    // Everything before the first blank line is the WEBVTT header
+    let body = match body.find("\n\n").or_else(|| body.find("\r\n\r\n")) {
+        Some(i) if body.trim_start().starts_with("WEBVTT") => &body[i..],
+        _ => body,
+    };
+    parse_arrow_blocks(body)
+}
+
+// SRT and VTT share the block layout: [id]\n<start> --> <end>[ settings]\n<text lines>
+fn parse_arrow_blocks(text: &str) -> Vec<SubCue> {
+    let mut cues = Vec::new();
+    let mut lines = text.lines().map(|l| l.trim_end_matches('\r')).peekable();
+    while let Some(line) = lines.next() {
+        let Some((a, b)) = line.split_once("-->") else { continue };
+        let start = parse_timestamp(a.trim());
+        // VTT cue settings follow the end time after whitespace
+        let end = parse_timestamp(b.split_whitespace().next().unwrap_or(""));
+        let mut body: Vec<&str> = Vec::new();
+        while let Some(l) = lines.peek() {
+            if l.trim().is_empty() { break; }
+            body.push(lines.next().unwrap_or(""));
+        }
+        if let (Some(s), Some(e)) = (start, end) {
+            let text = strip_angle_tags(&body.join("\n"));
+            push_cue(&mut cues, s, e, text);
+        }
+    }
+    finish(cues)
+}
+
+pub fn parse_ass(text: &str) -> Vec<SubCue> {
+    let mut cues = Vec::new();
+    let mut in_events = false;
+    // Default v4+ field order; replaced by the section's Format line
+    let mut fields: Vec<String> = ["layer", "start", "end", "style", "name", "marginl", "marginr", "marginv", "effect", "text"]
+        .iter().map(|s| s.to_string()).collect();
+    for raw in strip_bom(text).lines() {
+        let line = raw.trim();
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        if !in_events { continue; }
+        if let Some(rest) = line.strip_prefix("Format:") {
+            fields = rest.split(',').map(|f| f.trim().to_ascii_lowercase()).collect();
+            continue;
+        }
+        let Some(rest) = line.strip_prefix("Dialogue:") else { continue };
+        // Text is the last field and may itself contain commas
+        let parts: Vec<&str> = rest.splitn(fields.len(), ',').collect();
+        if parts.len() != fields.len() { continue; }
+        let get = |name: &str| fields.iter().position(|f| f == name).map(|i| parts[i].trim());
+        let (Some(s), Some(e), Some(t)) = (get("start"), get("end"), get("text")) else { continue };
+        // Vector drawings ({\p1}...) are typesetting, not dialogue
+        if t.contains("\\p1") { continue; }
+        if let (Some(s), Some(e)) = (parse_timestamp(s), parse_timestamp(e)) {
+            push_cue(&mut cues, s, e, strip_ass_tags(t));
+        }
+    }
+    finish(cues)
+}
+
+fn push_cue(cues: &mut Vec<SubCue>, start_s: f64, end_s: f64, text: String) {
+    let text = text.trim().to_string();
+    if end_s > start_s && !text.is_empty() {
+        cues.push(SubCue { index: 0, start_s, end_s, text });
+    }
+}
+
+fn finish(mut cues: Vec<SubCue>) -> Vec<SubCue> {
+    cues.sort_by(|a, b| a.start_s.partial_cmp(&b.start_s).unwrap_or(std::cmp::Ordering::Equal));
+    for (i, c) in cues.iter_mut().enumerate() { c.index = i + 1; }
+    cues
+}
+
+// True when two windows share at least half of the shorter one
+pub fn mostly_overlaps(a_s: f64, a_e: f64, b_s: f64, b_e: f64) -> bool {
+    let overlap = a_e.min(b_e) - a_s.max(b_s);
+    let shorter = (a_e - a_s).min(b_e - b_s);
+    overlap > 0.0 && overlap >= 0.5 * shorter
+}
+
+// Text of the cues that mostly overlap [start_s, end_s] (e.g. the translation
+// track for a primary line), joined in order
+pub fn overlapping_text(cues: &[SubCue], start_s: f64, end_s: f64) -> Option<String> {
+    let parts: Vec<&str> = cues.iter()
+        .filter(|c| mostly_overlaps(c.start_s, c.end_s, start_s, end_s))
+        .map(|c| c.text.as_str())
+        .collect();
+    if parts.is_empty() { None } else { Some(parts.join("\n")) }
+}
+
+// Accepts HH:MM:SS,mmm (SRT), [HH:]MM:SS.mmm (VTT) and H:MM:SS.cc (ASS)
+pub fn parse_timestamp(s: &str) -> Option<f64> {
+    let s = s.trim().replace(',', ".");
+    let parts: Vec<&str> = s.split(':').collect();
+    let (h, m, sec) = match parts.as_slice() {
+        [h, m, sec] => (h.parse::<f64>().ok()?, m.parse::<f64>().ok()?, sec.parse::<f64>().ok()?),
+        [m, sec] => (0.0, m.parse::<f64>().ok()?, sec.parse::<f64>().ok()?),
+        _ => return None,
+    };
+    let t = h * 3600.0 + m * 60.0 + sec;
+    if t.is_finite() && t >= 0.0 { Some(t) } else { None }
+}
+
+fn strip_bom(s: &str) -> &str {
+    s.strip_prefix('\u{feff}').unwrap_or(s)
+}
+
+// <i>, <b>, <font ...>, VTT voice/class spans and inline timestamps
+fn strip_angle_tags(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut depth = 0usize;
+    for ch in s.chars() {
+        match ch {
+            '<' => depth += 1,
+            '>' if depth > 0 => depth -= 1,
+            _ if depth == 0 => out.push(ch),
+            _ => {}
+        }
+    }
+    out.replace("&amp;", "&").replace("&lt;", "<").replace("&gt;", ">").replace("&nbsp;", " ")
+}
+
+// {\override} blocks plus ASS line-break escapes
+fn strip_ass_tags(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut in_block = false;
+    for ch in s.chars() {
+        match ch {
+            '{' => in_block = true,
+            '}' if in_block => in_block = false,
+            _ if !in_block => out.push(ch),
+            _ => {}
+        }
+    }
+    out.replace("\\N", "\n").replace("\\n", "\n").replace("\\h", " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_srt() {
+        let srt = "\u{feff}1\r\n00:00:01,500 --> 00:00:03,250\r\n<i>こんにちは</i>\r\n世界\r\n\r\n2\r\n00:01:02,000 --> 00:01:04,000\r\nNext\r\n";
+        let cues = parse_srt(srt);
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].text, "こんにちは\n世界");
+        assert!((cues[0].start_s - 1.5).abs() < 1e-9 && (cues[0].end_s - 3.25).abs() < 1e-9);
+        assert!((cues[1].start_s - 62.0).abs() < 1e-9);
+        assert_eq!(cues[1].index, 2);
+    }
+
+    #[test]
+    fn test_parse_vtt_short_timestamps_and_settings() {
+        let vtt = "WEBVTT - test\n\nNOTE a comment\n\nid-1\n00:05.000 --> 00:07.500 align:start position:10%\n<v Mika>Hello</v>\n";
+        let cues = parse_vtt(vtt);
+        assert_eq!(cues.len(), 1);
+        assert_eq!(cues[0].text, "Hello");
+        assert!((cues[0].end_s - 7.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_ass_events() {
+        let ass = "[Script Info]\nTitle: x\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+Dialogue: 0,0:00:10.00,0:00:12.50,Default,,0,0,0,,{\\an8}そうだね、\\Nでも\n\
+Comment: 0,0:00:11.00,0:00:12.00,Default,,0,0,0,,ignored\n\
+Dialogue: 0,0:00:03.00,0:00:04.00,Sign,,0,0,0,,{\\p1}m 0 0 l 10 10{\\p0}\n\
+Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,first, with comma\n";
+        let cues = parse_ass(ass);
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].text, "first, with comma");
+        assert_eq!(cues[1].text, "そうだね、\nでも");
+        assert!((cues[1].end_s - 12.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_overlapping_text_pairs_translation() {
+        let en = parse_srt("1\n00:00:01,000 --> 00:00:03,000\nHello\n\n2\n00:00:03,100 --> 00:00:04,000\nThere\n\n3\n00:00:09,000 --> 00:00:10,000\nLater\n");
+        // A Japanese line at 1.2-3.9 spans the first two English cues; a brief edge touch does not count
+        assert_eq!(overlapping_text(&en, 1.2, 3.9).as_deref(), Some("Hello\nThere"));
+        assert_eq!(overlapping_text(&en, 3.9, 8.0), None);
+        assert!(!mostly_overlaps(0.0, 1.0, 1.0, 2.0));
+    }
+
+    #[test]
+    fn test_parse_any_sniffs_format() {
+        assert_eq!(parse_any("WEBVTT\n\n00:01.000 --> 00:02.000\nA\n").len(), 1);
+        assert_eq!(parse_any("1\n00:00:01,000 --> 00:00:02,000\nB\n").len(), 1);
+    }
+}
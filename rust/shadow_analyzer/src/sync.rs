@@ -0,0 +1,106 @@
+// Global subtitle timing from recent cuts: each cut whose speech onset was seen gives the
+// sub-delay that would have put the subtitle start on it. The median over the last
+// RECENT_CUTS of one file is the suggestion, trusted once those cuts agree.
+
+use std::collections::VecDeque;
+use serde::Serialize;
+
+// Cuts the estimate looks back over
+pub const RECENT_CUTS: usize = 8;
+// Fewer cuts than this never make a suggestion
+const MIN_CUTS: usize = 3;
+// Median absolute deviation above which the cuts disagree (mixed timing, misdetections)
+const MAX_SPREAD_S: f64 = 0.15;
+
+// Estimate shown in the UI and applied on request
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SubOffset {
+    // Suggested sub-delay, and how far it is from the one the cut was made with
+    pub sub_delay: f64,
+    pub offset: f64,
+    // Median absolute deviation of the cuts from the suggestion
+    pub spread: f64,
+    pub cuts: usize,
+    // Enough cuts that agree: safe to apply
+    pub stable: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct OffsetEstimator {
+    media: String,
+    // Sub-delay that would have aligned each cut, oldest first
+    fits: VecDeque<f64>,
+}
+
+impl OffsetEstimator {
+    // One cut of `media`, made at `sub_delay`, whose speech began `lag` seconds after the
+    // subtitle start. Another file starts over.
+    pub fn push(&mut self, media: &str, sub_delay: f64, lag: f64) {
+        if media != self.media {
+            self.media = media.to_string();
+            self.fits.clear();
+        }
+        if self.fits.len() == RECENT_CUTS {
+            self.fits.pop_front();
+        }
+        self.fits.push_back(sub_delay + lag);
+    }
+
+    // Estimate for `media` relative to the current sub-delay; None before its first cut
+    pub fn estimate(&self, media: &str, sub_delay: f64) -> Option<SubOffset> {
+        if media != self.media || self.fits.is_empty() {
+            return None;
+        }
+        let fit = median(self.fits.iter().copied().collect());
+        let spread = median(self.fits.iter().map(|f| (f - fit).abs()).collect());
+        let cuts = self.fits.len();
+        Some(SubOffset { sub_delay: fit, offset: fit - sub_delay, spread, cuts, stable: cuts >= MIN_CUTS && spread <= MAX_SPREAD_S })
+    }
+}
+
+fn median(mut v: Vec<f64>) -> f64 {
+    v.sort_by(|a, b| a.total_cmp(b));
+    let mid = v.len() / 2;
+    if v.len() % 2 == 1 { v[mid] } else { 0.5 * (v[mid - 1] + v[mid]) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_is_robust_and_follows_sub_delay() {
+        let mut est = OffsetEstimator::default();
+        assert_eq!(est.estimate("ep1", 0.0), None);
+        // Speech 0.4 s after each subtitle, one misdetection
+        for lag in [0.41, 0.38, 0.42] {
+            est.push("ep1", 0.0, lag);
+        }
+        est.push("ep1", 0.0, -0.45);
+        let o = est.estimate("ep1", 0.0).unwrap();
+        assert!((o.sub_delay - 0.395).abs() < 1e-9 && o.stable && o.cuts == 4, "{:?}", o);
+        // After applying it, new cuts measure the residual; the fit stays put
+        est.push("ep1", 0.4, 0.01);
+        let o = est.estimate("ep1", 0.4).unwrap();
+        assert!((o.sub_delay - 0.41).abs() < 1e-9 && (o.offset - 0.01).abs() < 1e-9, "{:?}", o);
+        assert_eq!(est.estimate("ep2", 0.0), None);
+    }
+
+    #[test]
+    fn test_needs_agreeing_cuts_and_forgets_old_ones() {
+        let mut est = OffsetEstimator::default();
+        est.push("ep1", 0.0, 0.3);
+        est.push("ep1", 0.0, -0.2);
+        assert!(!est.estimate("ep1", 0.0).unwrap().stable);
+        est.push("ep1", 0.0, 0.5);
+        assert!(!est.estimate("ep1", 0.0).unwrap().stable);
+        for _ in 0..RECENT_CUTS {
+            est.push("ep1", 0.0, -0.1);
+        }
+        let o = est.estimate("ep1", 0.0).unwrap();
+        assert!(o.stable && o.cuts == RECENT_CUTS && o.spread == 0.0, "{:?}", o);
+        // Another file starts over
+        est.push("ep2", 0.0, 0.2);
+        assert_eq!(est.estimate("ep2", 0.0).unwrap().cuts, 1);
+    }
+}
@@ -0,0 +1,25 @@
+// Synthetic signals for the unit tests of the signal stages (VAD, dialogue isolation,
+// enhancement, pitch)
+
+use std::f32::consts::TAU;
+
+pub fn tone(sr: u32, hz: f32, amp: f32, n: usize) -> Vec<f32> {
+    (0..n).map(|i| amp * (TAU * hz * i as f32 / sr as f32).sin()).collect()
+}
+
+// Vowel-like: 140 Hz with decaying harmonics
+pub fn voice(sr: u32, n: usize) -> Vec<f32> {
+    (0..n).map(|i| {
+        let t = i as f32 / sr as f32;
+        (1..8).map(|h| (TAU * 140.0 * h as f32 * t).sin() * 0.3 / h as f32).sum()
+    }).collect()
+}
+
+// Deterministic white noise in [-amp, amp] (LCG); the seed picks the sequence
+pub fn noise(seed: u32, n: usize, amp: f32) -> Vec<f32> {
+    let mut x = seed;
+    (0..n).map(|_| {
+        x = x.wrapping_mul(1664525).wrapping_add(1013904223);
+        ((x >> 8) as f32 / (1u32 << 24) as f32 - 0.5) * 2.0 * amp
+    }).collect()
+}
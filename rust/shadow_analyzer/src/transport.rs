@@ -0,0 +1,125 @@
+// mpv JSON IPC transport: Windows named pipe or Unix domain socket.
+// Both carry the same newline-delimited JSON protocol, so callers only see
+// a Read + Write stream and never care which one is underneath.
+
+use std::io::{self, Read, Write};
+
+#[cfg(windows)]
+pub const DEFAULT_IPC_PATH: &str = r"\\.\\pipe\\MPVShadow";
+#[cfg(unix)]
+pub const DEFAULT_IPC_PATH: &str = "/tmp/mpvshadow.sock";
+
+// Environment override, used when no --ipc-server argument is given
+pub const IPC_PATH_ENV: &str = "MPVSHADOW_IPC";
+
+// Pick the IPC endpoint: CLI value first, then environment, then the platform default
+pub fn resolve_ipc_path(cli_value: Option<&str>) -> String {
+    if let Some(p) = cli_value.filter(|s| !s.is_empty()) {
+        return p.to_string();
+    }
+    if let Ok(p) = std::env::var(IPC_PATH_ENV) {
+        if !p.is_empty() { return p; }
+    }
+    DEFAULT_IPC_PATH.to_string()
+}
+
+// One connected IPC stream; clone it to get separate reader/writer handles
+#[derive(Debug)]
+pub enum IpcStream {
+    #[cfg(windows)]
+    Pipe(std::fs::File),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixStream),
+}
+
+impl IpcStream {
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            #[cfg(windows)]
+            IpcStream::Pipe(f) => Ok(IpcStream::Pipe(f.try_clone()?)),
+            #[cfg(unix)]
+            IpcStream::Unix(s) => Ok(IpcStream::Unix(s.try_clone()?)),
+        }
+    }
+}
+
+impl Read for IpcStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(windows)]
+            IpcStream::Pipe(f) => f.read(buf),
+            #[cfg(unix)]
+            IpcStream::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for IpcStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            #[cfg(windows)]
+            IpcStream::Pipe(f) => f.write(buf),
+            #[cfg(unix)]
+            IpcStream::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            #[cfg(windows)]
+            IpcStream::Pipe(f) => f.flush(),
+            #[cfg(unix)]
+            IpcStream::Unix(s) => s.flush(),
+        }
+    }
+}
+
+// Open the endpoint once; fails if mpv is not listening yet
+#[cfg(windows)]
+pub fn connect(path: &str) -> io::Result<IpcStream> {
+    let f = std::fs::OpenOptions::new().read(true).write(true).open(path)?;
+    Ok(IpcStream::Pipe(f))
+}
+
+#[cfg(unix)]
+pub fn connect(path: &str) -> io::Result<IpcStream> {
+    let s = std::os::unix::net::UnixStream::connect(path)?;
+    Ok(IpcStream::Unix(s))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn test_resolve_prefers_cli_value() {
+        assert_eq!(resolve_ipc_path(Some("/tmp/x.sock")), "/tmp/x.sock");
+    }
+
+    #[test]
+    fn test_unix_socket_roundtrip() {
+        let mut p = std::env::temp_dir();
+        p.push(format!("mpvshadow_transport_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&p);
+        let listener = UnixListener::bind(&p).unwrap();
+        let server = std::thread::spawn(move || {
+            let (s, _) = listener.accept().unwrap();
+            let mut r = BufReader::new(s.try_clone().unwrap());
+            let mut w = s;
+            let mut line = String::new();
+            r.read_line(&mut line).unwrap();
+            w.write_all(line.as_bytes()).unwrap();
+        });
+        let stream = connect(p.to_str().unwrap()).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        writer.write_all(b"{\"command\":[\"get_version\"]}\n").unwrap();
+        let mut echo = String::new();
+        reader.read_line(&mut echo).unwrap();
+        assert_eq!(echo.trim_end(), "{\"command\":[\"get_version\"]}");
+        server.join().unwrap();
+        let _ = std::fs::remove_file(&p);
+    }
+}
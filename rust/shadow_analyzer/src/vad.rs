@@ -0,0 +1,199 @@
+// Voice activity around a subtitle line: frame energy against the window's noise floor plus
+// the share of energy in the speech band, used to move the cut to where the line is spoken.
+
+use serde::Serialize;
+
+use crate::analysis;
+use crate::biquad::Biquad;
+use crate::decode::DecodedAudio;
+
+// How far past the subtitle timing speech is looked for (and the window extended at most)
+pub const SEARCH_S: f64 = 0.5;
+
+// 20 ms frames every 10 ms, at the analysis rate
+const FRAME_S: f64 = 0.020;
+const HOP_S: f64 = 0.010;
+// Speech band: above mains hum, below hiss and cymbals
+const BAND_LOW_HZ: f64 = 150.0;
+const BAND_HIGH_HZ: f64 = 4000.0;
+// Share of a frame's energy that must fall in the speech band
+const MIN_BAND_RATIO: f32 = 0.4;
+// Energy threshold: this far over the noise floor (10th percentile frame), but never more
+// than PEAK_RANGE_DB under the loudest frame (a window that is all speech has a high floor)
+const FLOOR_MARGIN_DB: f32 = 10.0;
+const PEAK_RANGE_DB: f32 = 15.0;
+// Quieter than this is never speech
+const MIN_SPEECH_DB: f32 = -60.0;
+// Pauses up to this long stay inside one utterance; bursts shorter than MIN_RUN_S are dropped
+const MAX_GAP_S: f64 = 0.200;
+const MIN_RUN_S: f64 = 0.080;
+
+// Original and adjusted windows of an auto-trimmed cut (audio stream seconds)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Trim {
+    // Padded window from the subtitle timing
+    pub orig_s: f64,
+    pub orig_e: f64,
+    // Speech found around the line, unpadded
+    pub speech_s: f64,
+    pub speech_e: f64,
+    // Padded window around the speech: what the clip holds
+    pub s: f64,
+    pub e: f64,
+}
+
+// Window to decode so speech up to SEARCH_S outside the padded window can be found
+pub fn search_window(s: f64, e: f64, dur: f64) -> (f64, f64) {
+    let end = e + SEARCH_S;
+    ((s - SEARCH_S).max(0.0), if dur > 0.0 { end.min(dur) } else { end })
+}
+
+// Speech decision per frame (FRAME_S every HOP_S) of mono audio
+pub fn speech_frames(mono: &[f32], sample_rate: u32) -> Vec<bool> {
+    let fs = sample_rate as f64;
+    let frame = (FRAME_S * fs).round() as usize;
+    let hop = (HOP_S * fs).round() as usize;
+    let total = analysis::frame_rms(mono, frame, hop);
+    let mut hp = Biquad::highpass(fs, BAND_LOW_HZ, std::f64::consts::FRAC_1_SQRT_2);
+    let mut lp = Biquad::lowpass(fs, BAND_HIGH_HZ.min(0.45 * fs), std::f64::consts::FRAC_1_SQRT_2);
+    let band: Vec<f32> = mono.iter().map(|&x| lp.process(hp.process(x as f64)) as f32).collect();
+    let band = analysis::frame_rms(&band, frame, hop);
+    if total.is_empty() { return Vec::new(); }
+
+    let db: Vec<f32> = total.iter().map(|&r| 20.0 * r.max(1e-9).log10()).collect();
+    let mut sorted = db.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let floor = sorted[sorted.len() / 10];
+    let peak = sorted[sorted.len() - 1];
+    let threshold = (floor + FLOOR_MARGIN_DB).min(peak - PEAK_RANGE_DB).max(MIN_SPEECH_DB);
+    db.iter().zip(total.iter().zip(&band))
+        .map(|(&d, (&t, &b))| d >= threshold && t > 0.0 && (b / t).powi(2) >= MIN_BAND_RATIO)
+        .collect()
+}
+
+// Speech runs as (first frame, end frame), after bridging pauses and dropping blips
+fn speech_runs(frames: &[bool]) -> Vec<(usize, usize)> {
+    let max_gap = (MAX_GAP_S / HOP_S).round() as usize;
+    let min_run = (MIN_RUN_S / HOP_S).round() as usize;
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let mut i = 0;
+    while i < frames.len() {
+        if !frames[i] { i += 1; continue; }
+        let start = i;
+        while i < frames.len() && frames[i] { i += 1; }
+        match runs.last_mut() {
+            Some(last) if start - last.1 <= max_gap => last.1 = i,
+            _ => runs.push((start, i)),
+        }
+    }
+    runs.retain(|r| r.1 - r.0 >= min_run);
+    runs
+}
+
+// Onset and offset of the speech overlapping [line_s, line_e], in the same timeline as
+// `start_s` (the time of mono[0]); at most SEARCH_S outside the line. None without speech.
+pub fn find_speech(mono: &[f32], sample_rate: u32, start_s: f64, line_s: f64, line_e: f64) -> Option<(f64, f64)> {
+    let frames = speech_frames(mono, sample_rate);
+    let frame_time = |f: usize| start_s + f as f64 * HOP_S;
+    let overlapping: Vec<(f64, f64)> = speech_runs(&frames)
+        .into_iter()
+        .map(|(a, b)| (frame_time(a), frame_time(b - 1) + FRAME_S))
+        .filter(|&(a, b)| a < line_e && b > line_s)
+        .collect();
+    if overlapping.is_empty() { return None; }
+    let onset = overlapping.iter().map(|r| r.0).fold(f64::INFINITY, f64::min);
+    let offset = overlapping.iter().map(|r| r.1).fold(f64::NEG_INFINITY, f64::max);
+    Some((onset.max(line_s - SEARCH_S), offset.min(line_e + SEARCH_S)))
+}
+
+// Speech around the line in a decoded search window starting at `decoded_s`
+pub fn speech_near(audio: &DecodedAudio, decoded_s: f64, line: (f64, f64)) -> Option<(f64, f64)> {
+    let (mono, sr) = analysis::analysis_mono(&audio.samples, audio.channels, audio.sample_rate);
+    find_speech(&mono, sr, decoded_s, line.0, line.1)
+}
+
+// How far the speech onset lags the subtitle start (negative: speech first), or None when
+// the onset was not seen: speech already running at the search limit or the decode start
+pub fn onset_lag(speech_s: f64, decoded_s: f64, line_s: f64) -> Option<f64> {
+    let limit = (line_s - SEARCH_S).max(decoded_s);
+    (speech_s - limit > HOP_S / 2.0).then_some(speech_s - line_s)
+}
+
+// Cut a decoded search window (starting at `decoded_s`) down to the padded `speech` found
+// around the line (speech_near), or to the original padded window [orig_s, orig_e] without it
+pub fn trim_to(
+    audio: &DecodedAudio,
+    decoded_s: f64,
+    speech: Option<(f64, f64)>,
+    orig: (f64, f64),
+    pad: f64,
+) -> (DecodedAudio, Option<Trim>) {
+    let decoded_e = decoded_s + audio.frames() as f64 / audio.sample_rate.max(1) as f64;
+    match speech {
+        Some((speech_s, speech_e)) => {
+            let (s, e) = ((speech_s - pad).max(decoded_s), (speech_e + pad).min(decoded_e));
+            let trim = Trim { orig_s: orig.0, orig_e: orig.1, speech_s, speech_e, s, e };
+            (audio.slice(s - decoded_s, e - decoded_s), Some(trim))
+        }
+        None => (audio.slice(orig.0 - decoded_s, orig.1 - decoded_s), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{noise, voice};
+
+    const SR: u32 = 24000;
+
+    fn at(s: f64) -> usize { (s * SR as f64) as usize }
+
+    #[test]
+    fn test_finds_speech_that_starts_late_and_ends_late() {
+        // 3 s of faint noise, speech 1.30-2.60 s with a 120 ms pause; subtitle says 1.0-2.2
+        let mut sig = noise(12345, at(3.0), 0.001);
+        let v = voice(SR, at(3.0));
+        for i in at(1.3)..at(2.6) {
+            if !(at(1.8)..at(1.92)).contains(&i) { sig[i] += v[i]; }
+        }
+        let (s, e) = find_speech(&sig, SR, 10.0, 11.0, 12.2).expect("speech");
+        assert!((s - 11.3).abs() < 0.03, "onset {}", s);
+        assert!((e - 12.6).abs() < 0.03, "offset {}", e);
+    }
+
+    #[test]
+    fn test_ignores_hum_and_silence() {
+        // Loud 50 Hz hum only: out of the speech band
+        let hum: Vec<f32> = (0..at(2.0)).map(|i| (std::f32::consts::TAU * 50.0 * i as f32 / SR as f32).sin() * 0.5).collect();
+        assert_eq!(find_speech(&hum, SR, 0.0, 0.5, 1.5), None);
+        assert_eq!(find_speech(&vec![0.0; at(2.0)], SR, 0.0, 0.5, 1.5), None);
+    }
+
+    #[test]
+    fn test_extension_is_bounded_and_neighbours_excluded() {
+        // Speech 0.2-2.8 s around a 1.5-1.6 line: clamped to SEARCH_S either side.
+        // A separate utterance at 3.2-3.6 s does not join.
+        let mut sig = noise(12345, at(4.0), 0.001);
+        let v = voice(SR, at(4.0));
+        for i in (at(0.2)..at(2.8)).chain(at(3.2)..at(3.6)) { sig[i] += v[i]; }
+        let (s, e) = find_speech(&sig, SR, 0.0, 1.5, 1.6).unwrap();
+        assert!((s - 1.0).abs() < 1e-9 && (e - 2.1).abs() < 1e-9, "{} {}", s, e);
+        let (_, e) = find_speech(&sig, SR, 0.0, 2.5, 2.7).unwrap();
+        assert!((e - 2.8).abs() < 0.03, "offset {}", e);
+    }
+
+    #[test]
+    fn test_search_window_clamps() {
+        assert_eq!(search_window(0.2, 1.0, 1.2), (0.0, 1.2));
+        assert_eq!(search_window(2.0, 3.0, 0.0), (1.5, 3.5));
+    }
+
+    #[test]
+    fn test_onset_lag_needs_a_seen_onset() {
+        assert_eq!(onset_lag(10.3, 9.4, 10.0), Some(10.3 - 10.0));
+        assert_eq!(onset_lag(9.8, 9.4, 10.0), Some(9.8 - 10.0));
+        // Clamped at the search limit, or running from the first decoded frame
+        assert_eq!(onset_lag(9.5, 9.4, 10.0), None);
+        assert_eq!(onset_lag(0.2, 0.2, 0.5), None);
+    }
+}
@@ -1,107 +1,630 @@
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 use anyhow::{Result, Context};
 
+// fmt tags
+const FORMAT_PCM: u16 = 1;
+const FORMAT_IEEE_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+// KSDATAFORMAT_SUBTYPE_* GUIDs are the format tag followed by these 14 bytes
+const SUBTYPE_GUID_TAIL: [u8; 14] = [0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71];
+// Extensible speaker bit of the LFE channel
+const SPEAKER_LOW_FREQUENCY: u32 = 0x8;
+// RIFF/data sizes that defer to the ds64 chunk in RF64 files
+const RF64_SIZE_MARKER: u32 = 0xFFFF_FFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+	// Unsigned, offset 128
+	Pcm8,
+	Pcm16,
+	Pcm24,
+	Pcm32,
+	Float32,
+	Float64,
+}
+
+impl SampleFormat {
+	pub fn parse(s: &str) -> Option<Self> {
+		match s.trim().to_ascii_lowercase().as_str() {
+			"pcm8" | "u8" => Some(Self::Pcm8),
+			"pcm16" | "s16" => Some(Self::Pcm16),
+			"pcm24" | "s24" => Some(Self::Pcm24),
+			"pcm32" | "s32" => Some(Self::Pcm32),
+			"float" | "f32" => Some(Self::Float32),
+			"double" | "f64" => Some(Self::Float64),
+			_ => None,
+		}
+	}
+
+	// From the fmt tag (or extensible subformat) and container bits
+	fn from_header(tag: u16, bits: u16) -> Option<Self> {
+		match (tag, bits) {
+			(FORMAT_PCM, 8) => Some(Self::Pcm8),
+			(FORMAT_PCM, 16) => Some(Self::Pcm16),
+			(FORMAT_PCM, 24) => Some(Self::Pcm24),
+			(FORMAT_PCM, 32) => Some(Self::Pcm32),
+			(FORMAT_IEEE_FLOAT, 32) => Some(Self::Float32),
+			(FORMAT_IEEE_FLOAT, 64) => Some(Self::Float64),
+			_ => None,
+		}
+	}
+
+	fn bits(self) -> u16 {
+		match self {
+			Self::Pcm8 => 8,
+			Self::Pcm16 => 16,
+			Self::Pcm24 => 24,
+			Self::Pcm32 | Self::Float32 => 32,
+			Self::Float64 => 64,
+		}
+	}
+
+	fn tag(self) -> u16 {
+		match self {
+			Self::Float32 | Self::Float64 => FORMAT_IEEE_FLOAT,
+			_ => FORMAT_PCM,
+		}
+	}
+
+	fn bytes(self) -> usize {
+		self.bits() as usize / 8
+	}
+
+	// One sample at the start of `b` as f32 (integer formats scaled to [-1, 1))
+	fn decode(self, b: &[u8]) -> f32 {
+		match self {
+			Self::Pcm8 => (b[0] as f32 - 128.0) / 128.0,
+			Self::Pcm16 => i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+			Self::Pcm24 => (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8388608.0,
+			Self::Pcm32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2147483648.0,
+			Self::Float32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+			Self::Float64 => f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as f32,
+		}
+	}
+
+	// PCM is clamped to [-1, 1]; float is stored as is
+	fn encode(self, x: f32, out: &mut Vec<u8>) {
+		let c = x.clamp(-1.0, 1.0) as f64;
+		match self {
+			Self::Pcm8 => out.push((c * 127.0).round() as i16 as u8 ^ 0x80),
+			Self::Pcm16 => out.extend_from_slice(&((c * 32767.0).round() as i16).to_le_bytes()),
+			Self::Pcm24 => out.extend_from_slice(&((c * 8388607.0).round() as i32).to_le_bytes()[..3]),
+			Self::Pcm32 => out.extend_from_slice(&((c * 2147483647.0).round() as i32).to_le_bytes()),
+			Self::Float32 => out.extend_from_slice(&x.to_le_bytes()),
+			Self::Float64 => out.extend_from_slice(&(x as f64).to_le_bytes()),
+		}
+	}
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct WavInfo {
 	pub sample_rate: u32,
 	pub channels: u16,
-	pub bits_per_sample: u16,
-}
-
-// Read minimal PCM 16-bit WAV and return mono f32 samples in [-1, 1] and the (possibly new) sample rate.
-// If target_sample_rate is Some(24000), performs simple 2x decimation when input is 48000 Hz.
-pub fn read_wav_mono_16bit(path: &Path, target_sample_rate: Option<u32>) -> Result<(Vec<f32>, u32)> {
-	let mut f = File::open(path).with_context(|| format!("open wav: {}", path.display()))?;
-	let mut buf = Vec::new();
-	f.read_to_end(&mut buf).with_context(|| "read wav bytes")?;
-
-	let (info, data_off, data_len) = parse_header_minimal(&buf)?;
-	if info.bits_per_sample != 16 {
-		anyhow::bail!("unsupported bits_per_sample: {}", info.bits_per_sample);
-	}
-	if data_off + data_len > buf.len() {
-		anyhow::bail!("wav data chunk out of bounds");
-	}
-	let bytes = &buf[data_off..data_off + data_len];
-	let total_samples = (bytes.len() / 2) as usize; // i16 samples interleaved
-	if total_samples == 0 { return Ok((Vec::new(), target_sample_rate.unwrap_or(info.sample_rate))); }
-
-	let ch = info.channels.max(1);
-	let frames = total_samples / ch as usize;
-	let mut mono: Vec<f32> = Vec::with_capacity(frames);
-	let mut i = 0usize;
-	for _ in 0..frames {
-		let mut acc: f32 = 0.0;
-		for _c in 0..ch {
-			let lo = bytes[i] as u16 as u32;
-			let hi = bytes[i + 1] as i8 as i32 as i64; // sign-extend via i8 -> i32 -> i64
-			let sample_i16 = ((hi as i32) << 8) | (lo as i32);
-			let sample = (sample_i16 as f32) / 32768.0;
-			acc += sample;
-			i += 2;
-		}
-		mono.push(acc / (ch as f32));
-	}
-
-	let out_sr = if let Some(tgt) = target_sample_rate { tgt } else { info.sample_rate };
-	if info.sample_rate == 48000 && out_sr == 24000 {
-		// 2x decimation with simple 2-tap averaging to reduce aliasing
-		let mut dec: Vec<f32> = Vec::with_capacity(mono.len() / 2 + 1);
-		let mut j = 0usize;
-		while j + 1 < mono.len() {
-			let v = 0.5 * (mono[j] + mono[j + 1]);
-			dec.push(v);
-			j += 2;
-		}
-		Ok((dec, out_sr))
+	pub format: SampleFormat,
+	// Speaker mask from an extensible header (0 = not given)
+	pub channel_mask: u32,
+}
+
+impl WavInfo {
+	// Mono mix of interleaved frames; the LFE channel of an extensible surround file stays out
+	pub fn mix_mono(&self, samples: &[f32]) -> Vec<f32> {
+		let ch = self.channels.max(1) as usize;
+		if ch == 1 { return samples.to_vec(); }
+		let mut keep = vec![true; ch];
+		let mut mask = self.channel_mask;
+		for k in keep.iter_mut() {
+			if mask == 0 { break; }
+			let bit = mask & mask.wrapping_neg();
+			*k = bit != SPEAKER_LOW_FREQUENCY;
+			mask &= !bit;
+		}
+		let used = keep.iter().filter(|k| **k).count().max(1) as f32;
+		samples.chunks_exact(ch)
+			.map(|f| f.iter().zip(&keep).filter(|(_, k)| **k).map(|(x, _)| x).sum::<f32>() / used)
+			.collect()
+	}
+}
+
+// Streaming reader for PCM (8/16/24/32-bit) or IEEE float (32/64-bit) WAVs, plain or
+// extensible, RIFF or RF64: header and metadata are read on open, the audio on demand in
+// blocks of frames, so hour-long recordings never have to fit in memory
+pub struct WavReader {
+	file: File,
+	pub info: WavInfo,
+	pub metadata: WavMetadata,
+	data_offset: u64,
+	frames: u64,
+	// Next frame read_frames returns
+	position: u64,
+	bytes: Vec<u8>,
+}
+
+impl WavReader {
+	pub fn open(path: &Path) -> Result<Self> {
+		let mut file = File::open(path).with_context(|| format!("open wav: {}", path.display()))?;
+		let chunks = walk_chunks(&mut file).with_context(|| format!("read wav header: {}", path.display()))?;
+		let info = parse_header(&chunks)?;
+		let metadata = parse_metadata(&chunks)?;
+		let block = (info.format.bytes() * info.channels as usize) as u64;
+		let mut reader = Self {
+			file,
+			info,
+			metadata,
+			data_offset: chunks.data_offset,
+			frames: chunks.data_len / block,
+			position: 0,
+			bytes: Vec::new(),
+		};
+		reader.seek(0)?;
+		Ok(reader)
+	}
+
+	// Whole frames in the data chunk
+	pub fn frames(&self) -> u64 {
+		self.frames
+	}
+
+	// Move to a frame (clamped to the end)
+	pub fn seek(&mut self, frame: u64) -> Result<()> {
+		self.position = frame.min(self.frames);
+		let block = (self.info.format.bytes() * self.info.channels as usize) as u64;
+		self.file.seek(SeekFrom::Start(self.data_offset + self.position * block)).context("seek wav")?;
+		Ok(())
+	}
+
+	// Up to max_frames interleaved frames into `out` (replacing its contents); 0 at the end
+	pub fn read_frames(&mut self, out: &mut Vec<f32>, max_frames: usize) -> Result<usize> {
+		let n = (max_frames as u64).min(self.frames - self.position) as usize;
+		let width = self.info.format.bytes();
+		self.bytes.resize(n * width * self.info.channels as usize, 0);
+		self.file.read_exact(&mut self.bytes).context("read wav data")?;
+		let format = self.info.format;
+		out.clear();
+		out.extend(self.bytes.chunks_exact(width).map(|b| format.decode(b)));
+		self.position += n as u64;
+		Ok(n)
+	}
+
+	// Interleaved blocks of up to `frames` frames from the current position to the end
+	pub fn blocks(&mut self, frames: usize) -> impl Iterator<Item = Result<Vec<f32>>> + '_ {
+		std::iter::from_fn(move || {
+			let mut block = Vec::new();
+			match self.read_frames(&mut block, frames.max(1)) {
+				Ok(0) => None,
+				Ok(_) => Some(Ok(block)),
+				Err(e) => Some(Err(e)),
+			}
+		})
+	}
+}
+
+// Read a WAV (any format WavReader takes) and return mono f32 samples in [-1, 1] and the (possibly new) sample rate.
+// With a target_sample_rate the mix is resampled to it (band-limited, no delay). Tests only:
+// the app streams with WavReader.
+#[cfg(test)]
+pub fn read_wav_mono(path: &Path, target_sample_rate: Option<u32>) -> Result<(Vec<f32>, u32)> {
+	let mut reader = WavReader::open(path)?;
+	let info = reader.info;
+	let out_sr = target_sample_rate.unwrap_or(info.sample_rate);
+	let mut resampler = crate::resample::Resampler::new(info.sample_rate, out_sr);
+	let mut mono = Vec::with_capacity(reader.frames() as usize);
+	for block in reader.blocks(1 << 16) {
+		resampler.push(&info.mix_mono(&block?), &mut mono);
+	}
+	resampler.finish(&mut mono);
+	Ok((mono, out_sr))
+}
+
+// A whole WAV already in memory (a pipe read to the end): format and interleaved samples.
+// Streams never get their sizes patched, so an oversized data chunk means "to the end".
+pub fn read_wav_bytes(bytes: &[u8]) -> Result<(WavInfo, Vec<f32>)> {
+	let chunks = walk_chunks(&mut std::io::Cursor::new(bytes)).context("read wav header")?;
+	let info = parse_header(&chunks)?;
+	let width = info.format.bytes();
+	let block = width * info.channels as usize;
+	let start = chunks.data_offset as usize;
+	let end = start + chunks.data_len as usize / block * block;
+	let samples = bytes[start..end].chunks_exact(width).map(|b| info.format.decode(b)).collect();
+	Ok((info, samples))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WavSpec {
+	pub sample_rate: u32,
+	// 1 (mono) or 2 (stereo)
+	pub channels: u16,
+	pub format: SampleFormat,
+}
+
+// Marker in a clip (`cue ` chunk), optionally named (`labl`) and spanning a region (`ltxt`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CuePoint {
+	pub id: u32,
+	// Sample frame the marker sits at
+	pub position: u32,
+	pub label: Option<String>,
+	// Region length in frames; 0 for a point marker
+	pub length: u32,
+}
+
+// LIST/INFO tags, cue points with their labels, and any other chunks.
+// Written between fmt and data; WavReader::open reads it back into WavReader::metadata.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WavMetadata {
+	// INFO tags in file order (INAM title, ICMT comment, ISFT software, ...)
+	pub info: Vec<([u8; 4], String)>,
+	pub cues: Vec<CuePoint>,
+	// Raw chunks (id, payload); odd sizes are padded on write. On read: every chunk
+	// that is not fmt/fact/ds64/data/cue/LIST.
+	pub chunks: Vec<([u8; 4], Vec<u8>)>,
+}
+
+impl WavMetadata {
+	pub fn info(&self, tag: &[u8; 4]) -> Option<&str> {
+		self.info.iter().find(|(t, _)| t == tag).map(|(_, v)| v.as_str())
+	}
+
+	// Replace or add an INFO tag
+	pub fn set_info(&mut self, tag: &[u8; 4], value: impl Into<String>) {
+		let value = value.into();
+		match self.info.iter_mut().find(|(t, _)| t == tag) {
+			Some(entry) => entry.1 = value,
+			None => self.info.push((*tag, value)),
+		}
+	}
+
+	// Add `note` to the ICMT comment, after what is already there
+	pub fn append_comment(&mut self, note: &str) {
+		let comment = match self.info(b"ICMT") {
+			Some(c) => format!("{}; {}", c, note),
+			None => note.to_string(),
+		};
+		self.set_info(b"ICMT", comment);
+	}
+
+	fn info_chunk(&self) -> Option<Vec<u8>> {
+		if self.info.is_empty() { return None; }
+		let mut list = b"INFO".to_vec();
+		for (id, value) in &self.info {
+			// NUL-terminated
+			let mut text = value.as_bytes().to_vec();
+			text.push(0);
+			push_chunk(&mut list, id, &text);
+		}
+		Some(list)
+	}
+
+	fn cue_chunk(&self) -> Option<Vec<u8>> {
+		if self.cues.is_empty() { return None; }
+		let mut cue = (self.cues.len() as u32).to_le_bytes().to_vec();
+		for c in &self.cues {
+			cue.extend_from_slice(&c.id.to_le_bytes());
+			cue.extend_from_slice(&c.position.to_le_bytes()); // play order position
+			cue.extend_from_slice(b"data");
+			cue.extend_from_slice(&0u32.to_le_bytes()); // chunk start
+			cue.extend_from_slice(&0u32.to_le_bytes()); // block start
+			cue.extend_from_slice(&c.position.to_le_bytes()); // sample offset
+		}
+		Some(cue)
+	}
+
+	// LIST/adtl with a labl per named cue and an ltxt ("rgn ") per region
+	fn adtl_chunk(&self) -> Option<Vec<u8>> {
+		let mut list = b"adtl".to_vec();
+		for c in &self.cues {
+			if let Some(label) = &c.label {
+				let mut labl = c.id.to_le_bytes().to_vec();
+				labl.extend_from_slice(label.as_bytes());
+				labl.push(0);
+				push_chunk(&mut list, b"labl", &labl);
+			}
+			if c.length > 0 {
+				let mut ltxt = c.id.to_le_bytes().to_vec();
+				ltxt.extend_from_slice(&c.length.to_le_bytes());
+				ltxt.extend_from_slice(b"rgn ");
+				ltxt.extend_from_slice(&[0; 8]); // country, language, dialect, code page
+				push_chunk(&mut list, b"ltxt", &ltxt);
+			}
+		}
+		if list.len() > 4 { Some(list) } else { None }
+	}
+}
+
+// Append a sub-chunk, padded to even length
+fn push_chunk(out: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+	out.extend_from_slice(id);
+	out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+	out.extend_from_slice(data);
+	if data.len() % 2 == 1 { out.push(0); }
+}
+
+// Header variants the writer can produce
+#[derive(Debug, Clone, Copy, Default)]
+struct Layout {
+	extensible: bool,
+	rf64: bool,
+}
+
+// Write interleaved f32 samples (clamped to [-1, 1] for PCM) as a WAV file.
+// Formats above 16 bits get a WAVE_FORMAT_EXTENSIBLE header, and audio past 4 GiB switches to RF64.
+// Goes through a sibling temp file and a rename, so readers never see a partial file.
+pub fn write(path: &Path, samples: &[f32], spec: WavSpec, meta: &WavMetadata) -> Result<()> {
+	let data_len = samples.len() as u64 * spec.format.bytes() as u64;
+	let layout = Layout {
+		extensible: spec.format.bits() > 16,
+		// Leaves room for the header chunks
+		rf64: data_len > u32::MAX as u64 - (1 << 20),
+	};
+	write_with_layout(path, samples, spec, meta, layout)
+}
+
+fn write_with_layout(path: &Path, samples: &[f32], spec: WavSpec, meta: &WavMetadata, layout: Layout) -> Result<()> {
+	if !(1..=2).contains(&spec.channels) {
+		anyhow::bail!("unsupported channel count {} (mono or stereo)", spec.channels);
+	}
+	let bytes_per_sample = spec.format.bytes();
+	let block_align = spec.channels * bytes_per_sample as u16;
+	let frames = samples.len() / spec.channels as usize;
+	let data_len = frames as u64 * block_align as u64;
+
+	let mut chunks: Vec<([u8; 4], Vec<u8>)> = Vec::new();
+	if layout.rf64 {
+		// Sizes are filled in below, once the other chunks are known
+		chunks.push((*b"ds64", vec![0; 28]));
+	}
+	let tag = if layout.extensible { FORMAT_EXTENSIBLE } else { spec.format.tag() };
+	let mut fmt = Vec::with_capacity(40);
+	fmt.extend_from_slice(&tag.to_le_bytes());
+	fmt.extend_from_slice(&spec.channels.to_le_bytes());
+	fmt.extend_from_slice(&spec.sample_rate.to_le_bytes());
+	fmt.extend_from_slice(&(spec.sample_rate * block_align as u32).to_le_bytes());
+	fmt.extend_from_slice(&block_align.to_le_bytes());
+	fmt.extend_from_slice(&spec.format.bits().to_le_bytes());
+	if layout.extensible {
+		// cbSize, valid bits, speaker mask (FC for mono, FL|FR for stereo), subformat GUID
+		let mask: u32 = if spec.channels == 1 { 0x4 } else { 0x3 };
+		fmt.extend_from_slice(&22u16.to_le_bytes());
+		fmt.extend_from_slice(&spec.format.bits().to_le_bytes());
+		fmt.extend_from_slice(&mask.to_le_bytes());
+		fmt.extend_from_slice(&spec.format.tag().to_le_bytes());
+		fmt.extend_from_slice(&SUBTYPE_GUID_TAIL);
+	} else if tag != FORMAT_PCM {
+		fmt.extend_from_slice(&0u16.to_le_bytes());
+	}
+	chunks.push((*b"fmt ", fmt));
+	if spec.format.tag() != FORMAT_PCM {
+		// Non-PCM formats carry a fact chunk with the frame count
+		chunks.push((*b"fact", (frames.min(u32::MAX as usize) as u32).to_le_bytes().to_vec()));
+	}
+	if let Some(info) = meta.info_chunk() {
+		chunks.push((*b"LIST", info));
+	}
+	if let Some(cue) = meta.cue_chunk() {
+		chunks.push((*b"cue ", cue));
+	}
+	if let Some(adtl) = meta.adtl_chunk() {
+		chunks.push((*b"LIST", adtl));
+	}
+	chunks.extend(meta.chunks.iter().cloned());
+
+	let header_len: u64 = 4 + chunks.iter().map(|(_, d)| 8 + d.len() as u64 + d.len() as u64 % 2).sum::<u64>() + 8;
+	let riff_len = header_len + data_len + data_len % 2;
+	let (riff_field, data_field) = if layout.rf64 {
+		let mut ds64 = Vec::with_capacity(28);
+		ds64.extend_from_slice(&riff_len.to_le_bytes());
+		ds64.extend_from_slice(&data_len.to_le_bytes());
+		ds64.extend_from_slice(&(frames as u64).to_le_bytes());
+		ds64.extend_from_slice(&0u32.to_le_bytes()); // no table entries
+		chunks[0].1 = ds64;
+		(RF64_SIZE_MARKER, RF64_SIZE_MARKER)
 	} else {
-		Ok((mono, info.sample_rate))
-	}
-}
-
-fn parse_header_minimal(buf: &[u8]) -> Result<(WavInfo, usize, usize)> {
-	if buf.len() < 44 { anyhow::bail!("wav too small"); }
-	if &buf[0..4] != b"RIFF" || &buf[8..12] != b"WAVE" { anyhow::bail!("not RIFF/WAVE"); }
-	let mut p = 12usize;
-	let mut info: Option<WavInfo> = None;
-	let mut data_off: Option<usize> = None;
-	let mut data_len: Option<usize> = None;
-	while p + 8 <= buf.len() {
-		let chunk_id = &buf[p..p + 4];
-		let chunk_size = u32::from_le_bytes([buf[p + 4], buf[p + 5], buf[p + 6], buf[p + 7]]) as usize;
-		let payload_off = p + 8;
-		if payload_off + chunk_size > buf.len() { anyhow::bail!("chunk OOB"); }
-		if chunk_id == b"fmt " {
-			if chunk_size < 16 { anyhow::bail!("fmt too small"); }
-			let audio_format = u16::from_le_bytes([buf[payload_off], buf[payload_off + 1]]);
-			let channels = u16::from_le_bytes([buf[payload_off + 2], buf[payload_off + 3]]);
-			let sample_rate = u32::from_le_bytes([
-				buf[payload_off + 4], buf[payload_off + 5], buf[payload_off + 6], buf[payload_off + 7]
-			]);
-			let bits_per_sample = u16::from_le_bytes([
-				buf[payload_off + 14], buf[payload_off + 15]
-			]);
-			if audio_format != 1 { anyhow::bail!("unsupported format {} (PCM only)", audio_format); }
-			info = Some(WavInfo { sample_rate, channels, bits_per_sample });
-		} else if chunk_id == b"data" {
-			data_off = Some(payload_off);
-			data_len = Some(chunk_size);
-		}
-		p = payload_off + chunk_size;
-	}
-	let info = info.context("missing fmt chunk")?;
-	let data_off = data_off.context("missing data chunk")?;
-	let data_len = data_len.context("missing data length")?;
-	Ok((info, data_off, data_len))
+		let riff = u32::try_from(riff_len)
+			.map_err(|_| anyhow::anyhow!("wav too large for RIFF ({} bytes of audio)", data_len))?;
+		(riff, data_len as u32)
+	};
+
+	let mut tmp = path.as_os_str().to_owned();
+	tmp.push(".part");
+	let tmp = std::path::PathBuf::from(tmp);
+	let f = File::create(&tmp).with_context(|| format!("create wav: {}", tmp.display()))?;
+	let mut w = BufWriter::new(f);
+	w.write_all(if layout.rf64 { b"RF64" } else { b"RIFF" })?;
+	w.write_all(&riff_field.to_le_bytes())?;
+	w.write_all(b"WAVE")?;
+	for (id, data) in &chunks {
+		w.write_all(id)?;
+		w.write_all(&(data.len() as u32).to_le_bytes())?;
+		w.write_all(data)?;
+		if data.len() % 2 == 1 { w.write_all(&[0])?; }
+	}
+	w.write_all(b"data")?;
+	w.write_all(&data_field.to_le_bytes())?;
+	let mut block = Vec::with_capacity(8192 * bytes_per_sample);
+	for chunk in samples[..frames * spec.channels as usize].chunks(8192) {
+		block.clear();
+		for &x in chunk { spec.format.encode(x, &mut block); }
+		w.write_all(&block)?;
+	}
+	if data_len % 2 == 1 { w.write_all(&[0])?; }
+	w.flush().with_context(|| format!("write wav: {}", tmp.display()))?;
+	drop(w);
+	std::fs::rename(&tmp, path).with_context(|| format!("rename to {}", path.display()))?;
+	Ok(())
+}
+
+// Top-level chunks of a WAVE file: every payload but the audio is loaded; the data chunk
+// is only located (offset and length, clamped to the bytes present)
+struct Chunks {
+	list: Vec<([u8; 4], Vec<u8>)>,
+	data_offset: u64,
+	data_len: u64,
+}
+
+// Walk the top-level chunks of a RIFF/RF64 WAVE file. Chunks are word-aligned (odd sizes
+// are followed by a pad byte), RF64 takes the data size from ds64, and a data chunk whose
+// size runs past the end (a file still being written, or sizes never patched) ends the walk.
+fn walk_chunks<R: Read + Seek>(r: &mut R) -> Result<Chunks> {
+	let end = r.seek(SeekFrom::End(0))?;
+	if end < 12 { anyhow::bail!("wav too small"); }
+	let mut head = [0u8; 12];
+	r.seek(SeekFrom::Start(0))?;
+	r.read_exact(&mut head)?;
+	let rf64 = match &head[0..4] {
+		b"RIFF" => false,
+		b"RF64" | b"BW64" => true,
+		_ => anyhow::bail!("not RIFF/WAVE"),
+	};
+	if &head[8..12] != b"WAVE" { anyhow::bail!("not RIFF/WAVE"); }
+
+	let mut list = Vec::new();
+	let mut data: Option<(u64, u64)> = None;
+	let mut ds64_data_len: Option<u64> = None;
+	let mut p = 12u64;
+	while p + 8 <= end {
+		let mut h = [0u8; 8];
+		r.seek(SeekFrom::Start(p))?;
+		r.read_exact(&mut h)?;
+		let id: [u8; 4] = [h[0], h[1], h[2], h[3]];
+		let offset = p + 8;
+		let mut size = u32_at(&h, 4) as u64;
+		let available = end - offset;
+		if &id == b"data" {
+			if rf64 && size == RF64_SIZE_MARKER as u64 {
+				size = ds64_data_len.context("RF64 without ds64 chunk")?;
+			}
+			data = Some((offset, size.min(available)));
+			// Nothing after the audio is needed
+			if size >= available { break; }
+		} else {
+			if size > available { anyhow::bail!("chunk '{}' runs past the end", String::from_utf8_lossy(&id)); }
+			let mut payload = vec![0u8; size as usize];
+			r.read_exact(&mut payload)?;
+			if &id == b"ds64" {
+				if size < 24 { anyhow::bail!("ds64 too small"); }
+				ds64_data_len = Some(u64_at(&payload, 8));
+			}
+			list.push((id, payload));
+		}
+		p = offset + size + (size & 1);
+	}
+	let (data_offset, data_len) = data.context("missing data chunk")?;
+	Ok(Chunks { list, data_offset, data_len })
+}
+
+fn u16_at(b: &[u8], o: usize) -> u16 {
+	u16::from_le_bytes([b[o], b[o + 1]])
+}
+
+fn u32_at(b: &[u8], o: usize) -> u32 {
+	u32::from_le_bytes([b[o], b[o + 1], b[o + 2], b[o + 3]])
+}
+
+fn u64_at(b: &[u8], o: usize) -> u64 {
+	u32_at(b, o) as u64 | (u32_at(b, o + 4) as u64) << 32
+}
+
+// Sub-chunks of a LIST payload (after its 4-byte list type), with the same padding rule
+fn list_items(payload: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+	let mut p = 4usize;
+	std::iter::from_fn(move || {
+		if p + 8 > payload.len() { return None; }
+		let id = &payload[p..p + 4];
+		let size = u32_at(payload, p + 4) as usize;
+		let data = &payload[p + 8..(p + 8 + size).min(payload.len())];
+		p += 8 + size + (size & 1);
+		Some((id, data))
+	})
+}
+
+// NUL-terminated (or not) text, lossily decoded
+fn zstr(b: &[u8]) -> String {
+	let end = b.iter().position(|&c| c == 0).unwrap_or(b.len());
+	String::from_utf8_lossy(&b[..end]).into_owned()
+}
+
+fn parse_header(chunks: &Chunks) -> Result<WavInfo> {
+	let f = chunks.list.iter().find(|(id, _)| id == b"fmt ").map(|(_, f)| f.as_slice()).context("missing fmt chunk")?;
+	if f.len() < 16 { anyhow::bail!("fmt too small"); }
+	let mut tag = u16_at(f, 0);
+	let channels = u16_at(f, 2);
+	let sample_rate = u32_at(f, 4);
+	let bits_per_sample = u16_at(f, 14);
+	let mut channel_mask = 0;
+	if tag == FORMAT_EXTENSIBLE {
+		if f.len() < 40 { anyhow::bail!("extensible fmt too small"); }
+		channel_mask = u32_at(f, 20);
+		let guid = &f[24..40];
+		if guid[2..] != SUBTYPE_GUID_TAIL { anyhow::bail!("unsupported extensible subformat"); }
+		tag = u16::from_le_bytes([guid[0], guid[1]]);
+	}
+	let format = SampleFormat::from_header(tag, bits_per_sample)
+		.with_context(|| format!("unsupported format {} with {} bits", tag, bits_per_sample))?;
+	if channels == 0 { anyhow::bail!("fmt with 0 channels"); }
+	Ok(WavInfo { sample_rate, channels, format, channel_mask })
+}
+
+// INFO tags, cue points (with adtl labels and region lengths) and the remaining chunks
+fn parse_metadata(chunks: &Chunks) -> Result<WavMetadata> {
+	let mut meta = WavMetadata::default();
+	let mut adtl = Vec::new();
+	for (chunk_id, payload) in &chunks.list {
+		match chunk_id {
+			b"fmt " | b"fact" | b"ds64" => {}
+			b"cue " => {
+				if payload.len() < 4 { anyhow::bail!("cue chunk too small"); }
+				let n = (u32_at(payload, 0) as usize).min((payload.len() - 4) / 24);
+				for i in 0..n {
+					let o = 4 + i * 24;
+					let (id, position) = (u32_at(payload, o), u32_at(payload, o + 20));
+					meta.cues.push(CuePoint { id, position, label: None, length: 0 });
+				}
+			}
+			b"LIST" if payload.len() >= 4 => match &payload[0..4] {
+				b"INFO" => {
+					for (id, data) in list_items(payload) {
+						meta.info.push(([id[0], id[1], id[2], id[3]], zstr(data)));
+					}
+				}
+				// Labels can precede the cue chunk; attached once all cues are known
+				b"adtl" => adtl.push(payload.as_slice()),
+				_ => meta.chunks.push((*chunk_id, payload.clone())),
+			},
+			_ => meta.chunks.push((*chunk_id, payload.clone())),
+		}
+	}
+	for (id, data) in adtl.into_iter().flat_map(list_items) {
+		if data.len() < 4 { continue; }
+		let cue_id = u32_at(data, 0);
+		let Some(cue) = meta.cues.iter_mut().find(|c| c.id == cue_id) else { continue };
+		match id {
+			b"labl" => cue.label = Some(zstr(&data[4..])),
+			b"ltxt" if data.len() >= 8 => cue.length = u32_at(data, 4),
+			_ => {}
+		}
+	}
+	Ok(meta)
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use std::fs; 
-	use std::path::PathBuf;
+	use std::fs;
+
+	struct WavAudio {
+		info: WavInfo,
+		metadata: WavMetadata,
+		samples: Vec<f32>,
+	}
+
+	// Whole file through the streaming reader
+	fn read_wav(path: &Path) -> Result<WavAudio> {
+		let mut reader = WavReader::open(path)?;
+		let mut samples = Vec::new();
+		reader.read_frames(&mut samples, reader.frames() as usize)?;
+		Ok(WavAudio { info: reader.info, metadata: reader.metadata, samples })
+	}
 
 	fn write_test_wav_i16(path: &Path, sr: u32, channels: u16, pcm: &[i16]) -> Result<()> {
 		let mut f = File::create(path).context("create wav")?;
@@ -147,23 +670,324 @@ mod tests {
 		tmp.push("test_stereo.wav");
 		let _ = fs::remove_file(&tmp);
 		write_test_wav_i16(&tmp, sr, 2, &interleaved).unwrap();
-		let (mono, out_sr) = read_wav_mono_16bit(&tmp, Some(24000)).unwrap();
+		let (mono, out_sr) = read_wav_mono(&tmp, Some(24000)).unwrap();
 		assert_eq!(out_sr, 24000);
 		assert_eq!(mono.len(), n / 2);
 		let _ = fs::remove_file(&tmp);
 	}
 
+	// (id, payload) of every top-level chunk
+	fn chunks(buf: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+		let mut out = Vec::new();
+		let mut p = 12;
+		while p + 8 <= buf.len() {
+			let id: [u8; 4] = buf[p..p + 4].try_into().unwrap();
+			let n = u32::from_le_bytes(buf[p + 4..p + 8].try_into().unwrap()) as usize;
+			let n = n.min(buf.len() - p - 8);
+			out.push((id, buf[p + 8..p + 8 + n].to_vec()));
+			p += 8 + n + n % 2;
+		}
+		out
+	}
+
+	#[test]
+	fn test_sample_format_parse() {
+		assert_eq!(SampleFormat::parse("PCM24"), Some(SampleFormat::Pcm24));
+		assert_eq!(SampleFormat::parse("f32"), Some(SampleFormat::Float32));
+		assert_eq!(SampleFormat::parse("u8"), Some(SampleFormat::Pcm8));
+		assert_eq!(SampleFormat::parse("s12"), None);
+	}
+
+	#[test]
+	fn test_write_formats() {
+		let samples = [0.5f32, -0.5, 1.5, -1.0];
+		let mut p = std::env::temp_dir();
+		p.push(format!("test_write_formats_{}.wav", std::process::id()));
+		// Above 16 bits the header is WAVE_FORMAT_EXTENSIBLE
+		for (format, block, tag) in [(SampleFormat::Pcm16, 4, 1u16), (SampleFormat::Pcm24, 6, 0xFFFE), (SampleFormat::Float32, 8, 0xFFFE)] {
+			write(&p, &samples, WavSpec { sample_rate: 44100, channels: 2, format }, &WavMetadata::default()).unwrap();
+			let buf = fs::read(&p).unwrap();
+			assert_eq!(u32::from_le_bytes(buf[4..8].try_into().unwrap()) as usize, buf.len() - 8);
+			let c = chunks(&buf);
+			let fmt = &c.iter().find(|(id, _)| id == b"fmt ").unwrap().1;
+			assert_eq!(u16::from_le_bytes([fmt[0], fmt[1]]), tag);
+			assert_eq!(u16::from_le_bytes([fmt[12], fmt[13]]), block);
+			let data = &c.iter().find(|(id, _)| id == b"data").unwrap().1;
+			assert_eq!(data.len(), 2 * block as usize);
+			match format {
+				// 1.5 is clamped for PCM, kept for float
+				SampleFormat::Pcm16 => assert_eq!(i16::from_le_bytes([data[4], data[5]]), 32767),
+				SampleFormat::Pcm24 => assert_eq!(&data[0..3], &[0x00, 0x00, 0x40]),
+				SampleFormat::Float32 => {
+					assert_eq!(f32::from_le_bytes(data[8..12].try_into().unwrap()), 1.5);
+					assert!(c.iter().any(|(id, d)| id == b"fact" && d == &2u32.to_le_bytes()));
+				}
+				_ => unreachable!(),
+			}
+		}
+		assert!(write(&p, &samples, WavSpec { sample_rate: 44100, channels: 3, format: SampleFormat::Pcm16 }, &WavMetadata::default()).is_err());
+		let _ = fs::remove_file(&p);
+	}
+
+	#[test]
+	fn test_write_metadata_chunks_are_padded_and_readable() {
+		let mut p = std::env::temp_dir();
+		p.push(format!("test_write_meta_{}.wav", std::process::id()));
+		let meta = WavMetadata {
+			info: vec![(*b"INAM", "こんにちは".to_string()), (*b"ISFT", "abc".to_string())],
+			chunks: vec![(*b"test", b"odd".to_vec())],
+			..Default::default()
+		};
+		let pcm: Vec<f32> = (0..480).map(|i| (i as f32 / 480.0) - 0.5).collect();
+		write(&p, &pcm, WavSpec { sample_rate: 48000, channels: 1, format: SampleFormat::Pcm16 }, &meta).unwrap();
+		let buf = fs::read(&p).unwrap();
+		let c = chunks(&buf);
+		let ids: Vec<&[u8; 4]> = c.iter().map(|(id, _)| id).collect();
+		assert_eq!(ids, vec![b"fmt ", b"LIST", b"test", b"data"]);
+		let list = &c[1].1;
+		assert_eq!(&list[0..4], b"INFO");
+		assert_eq!(&list[4..8], b"INAM");
+		let n = u32::from_le_bytes(list[8..12].try_into().unwrap()) as usize;
+		assert_eq!(&list[12..12 + n], "こんにちは\0".as_bytes());
+		assert_eq!(&list[12 + n..16 + n], b"ISFT");
+		assert_eq!(c[2].1, b"odd");
+		// The reader skips the pad byte after the odd-sized chunk
+		let (mono, sr) = read_wav_mono(&p, None).unwrap();
+		assert_eq!((mono.len(), sr), (480, 48000));
+		let _ = fs::remove_file(&p);
+	}
+
+	fn tmp_wav(name: &str) -> std::path::PathBuf {
+		std::env::temp_dir().join(format!("test_wav_{}_{}.wav", name, std::process::id()))
+	}
+
+	#[test]
+	fn test_round_trip_every_format_and_layout() {
+		let formats = [
+			(SampleFormat::Pcm8, 1.0 / 64.0),
+			(SampleFormat::Pcm16, 1e-4),
+			(SampleFormat::Pcm24, 1e-6),
+			(SampleFormat::Pcm32, 1e-7),
+			(SampleFormat::Float32, 0.0),
+			(SampleFormat::Float64, 0.0),
+		];
+		let p = tmp_wav("roundtrip");
+		for (format, tol) in formats {
+			for channels in [1u16, 2] {
+				for extensible in [false, true] {
+					let pcm: Vec<f32> = (0..301 * channels as usize).map(|i| ((i * 37 % 200) as f32 / 100.0) - 1.0).collect();
+					let spec = WavSpec { sample_rate: 22050, channels, format };
+					write_with_layout(&p, &pcm, spec, &WavMetadata::default(), Layout { extensible, rf64: false }).unwrap();
+					let a = read_wav(&p).unwrap();
+					let what = format!("{:?} ch={} ext={}", format, channels, extensible);
+					assert_eq!(a.info.format, format, "{}", what);
+					assert_eq!((a.info.sample_rate, a.info.channels), (22050, channels), "{}", what);
+					assert_eq!(a.info.channel_mask, if extensible { if channels == 1 { 0x4 } else { 0x3 } } else { 0 }, "{}", what);
+					assert_eq!(a.samples.len(), pcm.len(), "{}", what);
+					let err = pcm.iter().zip(&a.samples).map(|(x, y)| (x - y).abs()).fold(0f32, f32::max);
+					assert!(err <= tol, "{}: max error {}", what, err);
+				}
+			}
+		}
+		let _ = fs::remove_file(&p);
+	}
+
+	#[test]
+	fn test_rf64_round_trip() {
+		let p = tmp_wav("rf64");
+		let pcm: Vec<f32> = (0..1000).map(|i| (i as f32 / 1000.0) - 0.5).collect();
+		let spec = WavSpec { sample_rate: 48000, channels: 2, format: SampleFormat::Float32 };
+		let mut meta = WavMetadata::default();
+		meta.set_info(b"INAM", "long session");
+		write_with_layout(&p, &pcm, spec, &meta, Layout { extensible: true, rf64: true }).unwrap();
+		let buf = fs::read(&p).unwrap();
+		assert_eq!(&buf[0..4], b"RF64");
+		assert_eq!(&buf[4..8], &[0xFF; 4]);
+		let c = chunks(&buf);
+		assert_eq!(&c[0].0, b"ds64");
+		assert_eq!(u64::from_le_bytes(c[0].1[0..8].try_into().unwrap()) as usize, buf.len() - 8);
+		assert_eq!(u64::from_le_bytes(c[0].1[16..24].try_into().unwrap()), 500);
+		let a = read_wav(&p).unwrap();
+		assert_eq!(a.samples, pcm);
+		assert_eq!(a.metadata.info(b"INAM"), Some("long session"));
+		let _ = fs::remove_file(&p);
+	}
+
+	#[test]
+	fn test_cues_labels_and_info_round_trip() {
+		let p = tmp_wav("cues");
+		let mut meta = WavMetadata::default();
+		meta.set_info(b"INAM", "odd");
+		meta.set_info(b"ICMT", "ep01.mkv 1.000-2.000");
+		meta.set_info(b"INAM", "今日は");
+		meta.cues = vec![
+			CuePoint { id: 1, position: 2400, label: Some("line".to_string()), length: 9600 },
+			CuePoint { id: 2, position: 4800, label: Some("き".to_string()), length: 0 },
+			CuePoint { id: 3, position: 7200, label: None, length: 0 },
+		];
+		meta.chunks = vec![(*b"shdw", b"{}x".to_vec())];
+		let pcm = vec![0.25f32; 14400];
+		write(&p, &pcm, WavSpec { sample_rate: 48000, channels: 1, format: SampleFormat::Pcm16 }, &meta).unwrap();
+		let ids: Vec<[u8; 4]> = chunks(&fs::read(&p).unwrap()).into_iter().map(|(id, _)| id).collect();
+		assert_eq!(ids, vec![*b"fmt ", *b"LIST", *b"cue ", *b"LIST", *b"shdw", *b"data"]);
+		let a = read_wav(&p).unwrap();
+		assert_eq!(a.metadata, meta);
+		assert_eq!(a.metadata.info(b"INAM"), Some("今日は"));
+		assert_eq!(a.samples.len(), 14400);
+		let _ = fs::remove_file(&p);
+	}
+
+	#[test]
+	fn test_walker_handles_odd_chunks_and_labels_before_cues() {
+		// Hand-built: odd-sized unknown chunk, adtl before cue, odd INFO value, then data
+		let mut body = b"WAVE".to_vec();
+		let mut fmt = Vec::new();
+		fmt.extend_from_slice(&FORMAT_PCM.to_le_bytes());
+		fmt.extend_from_slice(&1u16.to_le_bytes());
+		fmt.extend_from_slice(&8000u32.to_le_bytes());
+		fmt.extend_from_slice(&16000u32.to_le_bytes());
+		fmt.extend_from_slice(&2u16.to_le_bytes());
+		fmt.extend_from_slice(&16u16.to_le_bytes());
+		push_chunk(&mut body, b"fmt ", &fmt);
+		push_chunk(&mut body, b"junk", b"abc");
+		let mut adtl = b"adtl".to_vec();
+		push_chunk(&mut adtl, b"labl", &[&7u32.to_le_bytes()[..], b"mora\0"].concat());
+		push_chunk(&mut body, b"LIST", &adtl);
+		let mut cue = 1u32.to_le_bytes().to_vec();
+		for v in [7u32, 0, u32::from_le_bytes(*b"data"), 0, 0, 3] { cue.extend_from_slice(&v.to_le_bytes()); }
+		push_chunk(&mut body, b"cue ", &cue);
+		let mut info = b"INFO".to_vec();
+		push_chunk(&mut info, b"IART", b"a");
+		push_chunk(&mut body, b"LIST", &info);
+		push_chunk(&mut body, b"data", &[0, 0, 0xff, 0x7f, 0, 0x80, 0, 0]);
+		let mut buf = b"RIFF".to_vec();
+		buf.extend_from_slice(&(body.len() as u32).to_le_bytes());
+		buf.extend_from_slice(&body);
+		let p = tmp_wav("walker");
+		fs::write(&p, &buf).unwrap();
+		let a = read_wav(&p).unwrap();
+		assert_eq!(a.samples.len(), 4);
+		assert!((a.samples[1] - 1.0).abs() < 1e-3 && (a.samples[2] + 1.0).abs() < 1e-6);
+		assert_eq!(a.metadata.cues, vec![CuePoint { id: 7, position: 3, label: Some("mora".to_string()), length: 0 }]);
+		assert_eq!(a.metadata.info, vec![(*b"IART", "a".to_string())]);
+		assert_eq!(a.metadata.chunks, vec![(*b"junk", b"abc".to_vec())]);
+		let _ = fs::remove_file(&p);
+	}
+
+	#[test]
+	fn test_streaming_reader_blocks_and_seek() {
+		let p = tmp_wav("stream");
+		// Stereo ramp: frame i is (i, -i) / 10000, with metadata after fmt
+		let pcm: Vec<f32> = (0..10000).flat_map(|i| [i as f32 / 10000.0, -(i as f32) / 10000.0]).collect();
+		let mut meta = WavMetadata::default();
+		meta.set_info(b"INAM", "long");
+		write(&p, &pcm, WavSpec { sample_rate: 8000, channels: 2, format: SampleFormat::Float32 }, &meta).unwrap();
+		let mut r = WavReader::open(&p).unwrap();
+		assert_eq!((r.frames(), r.metadata.info(b"INAM")), (10000, Some("long")));
+		let sizes: Vec<usize> = r.blocks(4096).map(|b| b.unwrap().len()).collect();
+		assert_eq!(sizes, vec![8192, 8192, 3616]);
+		r.seek(7500).unwrap();
+		let mut block = Vec::new();
+		assert_eq!(r.read_frames(&mut block, 2).unwrap(), 2);
+		assert_eq!(block, &pcm[15000..15004]);
+		assert_eq!(r.info.mix_mono(&block), vec![0.0, 0.0]);
+		// Past the end clamps; nothing more to read
+		r.seek(20000).unwrap();
+		assert_eq!(r.read_frames(&mut block, 10).unwrap(), 0);
+		assert!(block.is_empty());
+		let _ = fs::remove_file(&p);
+	}
+
+	#[test]
+	fn test_streaming_reader_feeds_the_pitch_tracker() {
+		// 2 s of 180 Hz at the tracker's rate, F0 taken from 0.5 s on in small blocks
+		let p = tmp_wav("stream_f0");
+		let sr = 24000;
+		let tone: Vec<f32> = (0..2 * sr).map(|i| 0.4 * (std::f32::consts::TAU * 180.0 * i as f32 / sr as f32).sin()).collect();
+		write(&p, &tone, WavSpec { sample_rate: sr as u32, channels: 1, format: SampleFormat::Pcm16 }, &WavMetadata::default()).unwrap();
+		let cfg = crate::pitch::F0Config { sample_rate_hz: sr as f32, ..Default::default() };
+		let mut r = WavReader::open(&p).unwrap();
+		r.seek(sr as u64 / 2).unwrap();
+		let (info, mut stream) = (r.info, crate::pitch::F0Stream::new(&cfg));
+		for block in r.blocks(1000) {
+			stream.push(&info.mix_mono(&block.unwrap()));
+		}
+		let streamed = stream.finish();
+		let (mono, _) = read_wav_mono(&p, None).unwrap();
+		let whole = crate::pitch::estimate_f0(&mono[sr / 2..], &cfg);
+		assert_eq!(streamed.f0_hz, whole.f0_hz);
+		assert!((streamed.median_hz.unwrap() - 180.0).abs() < 3.0);
+		let _ = fs::remove_file(&p);
+	}
+
+	#[test]
+	fn test_read_unpatched_sizes_and_rejects_unknown_formats() {
+		// A recorder killed mid-write (or a pipe) leaves placeholder sizes: read what is there
+		let p = tmp_wav("unpatched");
+		let pcm: Vec<f32> = (0..100).map(|i| i as f32 / 200.0).collect();
+		write(&p, &pcm, WavSpec { sample_rate: 8000, channels: 1, format: SampleFormat::Pcm16 }, &WavMetadata::default()).unwrap();
+		let mut buf = fs::read(&p).unwrap();
+		let data_at = buf.windows(4).position(|w| w == b"data").unwrap();
+		buf[4..8].copy_from_slice(&[0xFF; 4]);
+		buf[data_at + 4..data_at + 8].copy_from_slice(&[0xFF; 4]);
+		buf.truncate(buf.len() - 21); // mid-sample
+		fs::write(&p, &buf).unwrap();
+		let a = read_wav(&p).unwrap();
+		assert_eq!(a.samples.len(), 89);
+		// 12-bit PCM is not a container size we know
+		buf[34] = 12;
+		fs::write(&p, &buf).unwrap();
+		assert!(read_wav(&p).is_err());
+		let _ = fs::remove_file(&p);
+	}
+
+	#[test]
+	fn test_read_wav_bytes_from_a_pipe() {
+		// ffmpeg writing float WAV to a pipe leaves both sizes at 0xFFFFFFFF
+		let p = tmp_wav("pipe");
+		let pcm: Vec<f32> = (0..64).map(|i| i as f32 / 64.0 - 0.5).collect();
+		write(&p, &pcm, WavSpec { sample_rate: 48000, channels: 2, format: SampleFormat::Float32 }, &WavMetadata::default()).unwrap();
+		let mut buf = fs::read(&p).unwrap();
+		let data_at = buf.windows(4).position(|w| w == b"data").unwrap();
+		buf[4..8].copy_from_slice(&[0xFF; 4]);
+		buf[data_at + 4..data_at + 8].copy_from_slice(&[0xFF; 4]);
+		let (info, samples) = read_wav_bytes(&buf).unwrap();
+		assert_eq!((info.sample_rate, info.channels), (48000, 2));
+		assert_eq!(samples, pcm);
+		assert!(read_wav_bytes(b"RIFF").is_err());
+		let _ = fs::remove_file(&p);
+	}
+
+	#[test]
+	fn test_mono_mix_skips_lfe() {
+		// FL FR FC LFE, as an extensible 24-bit header would come from Audacity;
+		// patch a stereo file's fmt to four channels with a 0x0F mask
+		let p = tmp_wav("lfe");
+		let frame = [0.25f32, 0.25, 0.25, 0.9];
+		write(&p, &frame, WavSpec { sample_rate: 48000, channels: 2, format: SampleFormat::Pcm24 }, &WavMetadata::default()).unwrap();
+		let mut buf = fs::read(&p).unwrap();
+		let fmt = buf.windows(4).position(|w| w == b"fmt ").unwrap() + 8;
+		buf[fmt + 2..fmt + 4].copy_from_slice(&4u16.to_le_bytes());
+		buf[fmt + 12..fmt + 14].copy_from_slice(&12u16.to_le_bytes());
+		buf[fmt + 20..fmt + 24].copy_from_slice(&0x0Fu32.to_le_bytes());
+		fs::write(&p, &buf).unwrap();
+		let (mono, _) = read_wav_mono(&p, None).unwrap();
+		assert_eq!(mono.len(), 1);
+		assert!((mono[0] - 0.25).abs() < 1e-5, "{}", mono[0]);
+		let _ = fs::remove_file(&p);
+	}
+
 	#[test]
 	fn test_read_wav_mono_passthrough() {
 		let sr = 48000u32;
 		let n = 3200usize;
 		let mut mono_i16: Vec<i16> = Vec::with_capacity(n);
-		for i in 0..n { mono_i16.push(((i as f32 / n as f32) * 2.0 - 1.0) as f32 as i16); }
-		let mut p = PathBuf::from(std::env::temp_dir());
+		for i in 0..n { mono_i16.push(((i as f32 / n as f32) * 2.0 - 1.0) as i16); }
+		let mut p = std::env::temp_dir();
 		p.push("test_mono.wav");
 		let _ = fs::remove_file(&p);
 		write_test_wav_i16(&p, sr, 1, &mono_i16).unwrap();
-		let (mono, out_sr) = read_wav_mono_16bit(&p, None).unwrap();
+		let (mono, out_sr) = read_wav_mono(&p, None).unwrap();
 		assert_eq!(out_sr, sr);
 		assert_eq!(mono.len(), n);
 		let _ = fs::remove_file(&p);